@@ -1,17 +1,14 @@
-extern crate flate2;
 extern crate nbtrs;
 
 use nbtrs::Tag;
 
-use flate2::read::GzDecoder;
 use std::env::args;
 use std::fs::File;
 
 fn load_and_print(s: &str) {
     println!("Dumping... {}", s);
-    let f = File::open(s).unwrap();
-    let mut decoder = GzDecoder::new(f);
-    let (name, tag) = Tag::parse(&mut decoder).unwrap();
+    let mut f = File::open(s).unwrap();
+    let (name, tag) = Tag::parse_compressed(&mut f).unwrap();
 
     tag.pretty_print(0, Some(&name));
 }
@@ -0,0 +1,119 @@
+//! Endian-aware reading and writing of numbers
+//!
+//! Normally this just re-exports what's needed from the `byteorder` crate.  With the `minimal`
+//! feature enabled (or the `byteorder` feature disabled), hand-written versions with the same
+//! API are used instead, so that `byteorder` isn't needed at all.
+
+#[cfg(all(feature = "byteorder", not(feature = "minimal")))]
+pub use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+#[cfg(any(feature = "minimal", not(feature = "byteorder")))]
+pub use self::manual::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+#[cfg(any(feature = "minimal", not(feature = "byteorder")))]
+mod manual {
+    use std::io::{self, Read, Write};
+
+    /// A byte order, expressed as how to convert to and from big-endian
+    pub trait ByteOrder {
+        /// Reorders big-endian bytes into this byte order (or back again)
+        fn from_be<const N: usize>(bytes: [u8; N]) -> [u8; N];
+    }
+
+    pub enum BigEndian {}
+
+    impl ByteOrder for BigEndian {
+        fn from_be<const N: usize>(bytes: [u8; N]) -> [u8; N] {
+            bytes
+        }
+    }
+
+    pub enum LittleEndian {}
+
+    impl ByteOrder for LittleEndian {
+        fn from_be<const N: usize>(mut bytes: [u8; N]) -> [u8; N] {
+            bytes.reverse();
+            bytes
+        }
+    }
+
+    // a helper to define read_u16, etc.
+    macro_rules! reader {
+        ($name:ident, $t:ty) => {
+            fn $name<B: ByteOrder>(&mut self) -> io::Result<$t> {
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                self.read_exact(&mut buf)?;
+                Ok(<$t>::from_be_bytes(B::from_be(buf)))
+            }
+        };
+    }
+
+    // a helper to define write_u16, etc.
+    macro_rules! writer {
+        ($name:ident, $t:ty) => {
+            fn $name<B: ByteOrder>(&mut self, n: $t) -> io::Result<()> {
+                self.write_all(&B::from_be(n.to_be_bytes()))
+            }
+        };
+    }
+
+    pub trait ReadBytesExt: Read {
+        fn read_u8(&mut self) -> io::Result<u8> {
+            let mut buf = [0; 1];
+            self.read_exact(&mut buf)?;
+            Ok(buf[0])
+        }
+        fn read_i8(&mut self) -> io::Result<i8> {
+            self.read_u8().map(|b| b as i8)
+        }
+
+        reader!(read_u16, u16);
+        reader!(read_i16, i16);
+        reader!(read_u32, u32);
+        reader!(read_i32, i32);
+        reader!(read_i64, i64);
+        reader!(read_f32, f32);
+        reader!(read_f64, f64);
+    }
+
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    pub trait WriteBytesExt: Write {
+        fn write_u8(&mut self, n: u8) -> io::Result<()> {
+            self.write_all(&[n])
+        }
+        fn write_i8(&mut self, n: i8) -> io::Result<()> {
+            self.write_u8(n as u8)
+        }
+
+        writer!(write_u16, u16);
+        writer!(write_i16, i16);
+        writer!(write_u32, u32);
+        writer!(write_i32, i32);
+        writer!(write_i64, i64);
+        writer!(write_f32, f32);
+        writer!(write_f64, f64);
+    }
+
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use std::io::Cursor;
+
+        #[test]
+        fn test_byte_orders() {
+            let mut buf = Vec::new();
+            buf.write_i32::<BigEndian>(-2).unwrap();
+            buf.write_f64::<LittleEndian>(1.5).unwrap();
+            assert_eq!(&buf[..4], &(-2i32).to_be_bytes());
+            assert_eq!(&buf[4..], &1.5f64.to_le_bytes());
+
+            let mut cur = Cursor::new(buf);
+            assert_eq!(cur.read_i32::<BigEndian>().unwrap(), -2);
+            assert_eq!(cur.read_f64::<LittleEndian>().unwrap(), 1.5);
+            assert!(cur.read_u8().is_err());
+        }
+    }
+}
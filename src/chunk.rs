@@ -0,0 +1,702 @@
+//! Helpers for pulling commonly needed data out of Anvil chunks
+//!
+//! These all take the root `Tag` of a chunk, as returned by `RegionFile::load_chunk`, and
+//! understand the layout differences between Minecraft versions.
+
+use std::borrow::Cow;
+
+use crate::nbt::{Tag, Taglike};
+
+/// The DataVersion of the first 1.18 snapshot (21w43a), which moved everything out of the
+/// `Level` compound and renamed a number of keys
+const DATA_VERSION_1_18: i32 = 2844;
+
+/// Returns the DataVersion of a chunk, which is missing in chunks from before 1.9
+fn data_version(chunk: &Tag) -> Option<i32> {
+    chunk.key("DataVersion").as_i32()
+}
+
+/// Returns the list of sections in a chunk
+///
+/// These live under `Level/Sections` before 1.18, and under a top-level `sections` since.
+fn sections(chunk: &Tag) -> Option<&Vec<Tag>> {
+    chunk
+        .key("sections")
+        .as_list()
+        .or_else(|| chunk.key("Level").key("Sections").as_list())
+}
+
+/// Returns the height of the world a chunk belongs to, in blocks
+///
+/// 1.18 extended worlds from 0 - 255 to -64 - 319.
+fn world_height(chunk: &Tag) -> u32 {
+    if data_version(chunk).is_some_and(|v| v >= DATA_VERSION_1_18) {
+        384
+    } else {
+        256
+    }
+}
+
+/// Is this a block state name for one of the air blocks
+fn is_air(name: &str) -> bool {
+    name == "minecraft:air" || name == "minecraft:cave_air" || name == "minecraft:void_air"
+}
+
+/// Unpacks `count` entries of `bits` bits each from a packed long array.
+///
+/// Since 1.16 entries never span two longs (leaving padding at the top of each long), while
+/// older versions pack entries tightly across long boundaries.  Which layout is in use is
+/// worked out from the length of the array.  Returns `None` if the length doesn't match
+/// either layout.
+fn unpack(longs: &[i64], bits: u32, count: usize) -> Option<Vec<u64>> {
+    if bits == 0 || bits > 64 {
+        return None;
+    }
+    let mask = if bits == 64 { !0 } else { (1u64 << bits) - 1 };
+    let per_long = (64 / bits) as usize;
+
+    let mut v = Vec::with_capacity(count);
+    if longs.len() == count.div_ceil(per_long) {
+        for i in 0..count {
+            let shift = (i % per_long) as u32 * bits;
+            v.push((longs[i / per_long] as u64 >> shift) & mask);
+        }
+    } else if longs.len() == (count * bits as usize).div_ceil(64) {
+        for i in 0..count {
+            let bit = i * bits as usize;
+            let (idx, shift) = (bit / 64, (bit % 64) as u32);
+            let mut value = longs[idx] as u64 >> shift;
+            if shift + bits > 64 {
+                value |= (longs[idx + 1] as u64) << (64 - shift);
+            }
+            v.push(value & mask);
+        }
+    } else {
+        return None;
+    }
+    Some(v)
+}
+
+/// Counts the non-air blocks in a single section
+fn section_non_air_count(section: &Tag) -> Option<u64> {
+    // 1.18+ nests the palette and data in a block_states compound, 1.13 - 1.17 keep them
+    // directly in the section
+    let (palette, data) = if let Some(states) = section.key("block_states") {
+        (
+            states.key("palette").as_list(),
+            states.key("data").as_longs(),
+        )
+    } else {
+        (
+            section.key("Palette").as_list(),
+            section.key("BlockStates").as_longs(),
+        )
+    };
+
+    let palette = match palette {
+        Some(palette) => palette,
+        None => {
+            // pre-flattening sections store numeric ids, where id 0 is air
+            let blocks = match section.key("Blocks").as_bytes() {
+                Some(blocks) => blocks,
+                // sections holding only light data (like those 1.14 - 1.17 keep just above
+                // and below the world) have no blocks at all
+                None if section.key("block_states").is_none() => return Some(0),
+                None => return None,
+            };
+            let add = section.key("Add").as_bytes();
+            let count = blocks
+                .iter()
+                .enumerate()
+                .filter(|&(i, &b)| {
+                    b != 0
+                        || add
+                            .and_then(|a| a.get(i / 2))
+                            .is_some_and(|&a| (a >> ((i % 2) * 4)) & 0xf != 0)
+                })
+                .count();
+            return Some(count as u64);
+        }
+    };
+
+    let air: Vec<bool> = palette
+        .iter()
+        .map(|entry| entry.key("Name").as_string().is_some_and(|n| is_air(n)))
+        .collect();
+
+    match data {
+        // a section with a single palette entry has no data array
+        None if air.len() == 1 => Some(if air[0] { 0 } else { 4096 }),
+        None => None,
+        Some(data) => {
+            let bits = (usize::BITS - (air.len().max(1) - 1).leading_zeros()).max(4);
+            let indices = unpack(data, bits, 4096)?;
+            let mut count = 0;
+            for idx in indices {
+                if !*air.get(idx as usize)? {
+                    count += 1;
+                }
+            }
+            Some(count)
+        }
+    }
+}
+
+/// Counts the number of non-air blocks in a chunk.
+///
+/// This works with both palette-based sections (1.13+) and the older numeric block id
+/// sections.  `minecraft:cave_air` and `minecraft:void_air` are counted as air, as are sections
+/// with no block data at all.  Returns `None` if the chunk has no sections list, or if any
+/// section is malformed.
+pub fn non_air_block_count(chunk: &Tag) -> Option<u64> {
+    sections(chunk)?.iter().map(section_non_air_count).sum()
+}
+
+/// Decodes one of a chunk's heightmaps, like `MOTION_BLOCKING` or `WORLD_SURFACE`.
+///
+/// Heightmaps are read from `Heightmaps/<which>` (under `Level` before 1.18), and hold 256
+/// values in x-major order, each giving the height above the bottom of the world.  The number
+/// of bits per value depends on the world height.  Returns `None` if the heightmap is missing
+/// or malformed.  Chunks from before 1.13 have a single `Level/HeightMap` int array instead,
+/// which isn't handled here.
+pub fn heightmap(chunk: &Tag, which: &str) -> Option<Vec<u16>> {
+    let longs = chunk
+        .key("Heightmaps")
+        .or_else(|| chunk.key("Level").key("Heightmaps"))
+        .key(which)
+        .as_longs()?;
+    let bits = u32::BITS - world_height(chunk).leading_zeros();
+    let values = unpack(longs, bits, 256)?;
+    Some(values.into_iter().map(|v| v as u16).collect())
+}
+
+/// Returns the biome at a block in a chunk.
+///
+/// `x` and `z` are block coordinates within the chunk (0 - 15), and `y` is the absolute block
+/// height.  Since 1.18 biomes are stored per section as a palette of names, which are
+/// returned as-is.  Older chunks have a `Level/Biomes` array of numeric ids instead, and for
+/// those the id is returned as a string, like `"4"`.  That's a 256 entry array with one id per
+/// column before 1.15 (a byte array before 1.13, and an int array since), and a 1024 entry int
+/// array with one id per 4x4x4 cell from 1.15.  Returns `None` if there is no biome data for
+/// the block.
+pub fn biome_at(chunk: &Tag, x: u8, y: i32, z: u8) -> Option<Cow<'_, str>> {
+    if x >= 16 || z >= 16 {
+        return None;
+    }
+    let (x, z) = (x as usize, z as usize);
+
+    if let Some(sections) = chunk.key("sections").as_list() {
+        let section = sections
+            .iter()
+            .find(|s| s.key("Y").as_i8().map(i32::from) == Some(y.div_euclid(16)))?;
+        let biomes = section.key("biomes");
+        let palette = biomes.key("palette").as_list()?;
+        let idx = match biomes.key("data").as_longs() {
+            // a section with a single biome has no data array
+            None => 0,
+            Some(data) => {
+                let bits = usize::BITS - (palette.len().max(1) - 1).leading_zeros();
+                let cell = (y.rem_euclid(16) as usize / 4) * 16 + (z / 4) * 4 + x / 4;
+                *unpack(data, bits, 64)?.get(cell)? as usize
+            }
+        };
+        return palette
+            .get(idx)
+            .as_string()
+            .map(|s| Cow::Borrowed(s.as_str()));
+    }
+
+    let id = match chunk.key("Level").key("Biomes")? {
+        Tag::TagByteArray(v) => *v.get(z * 16 + x)? as i32,
+        // 1.13 - 1.14 still have one entry per column
+        Tag::TagIntArray(v) if v.len() == 256 => v[z * 16 + x],
+        Tag::TagIntArray(v) if v.len() == 1024 => {
+            if !(0..256).contains(&y) {
+                return None;
+            }
+            v[(y as usize / 4) * 16 + (z / 4) * 4 + x / 4]
+        }
+        _ => return None,
+    };
+    Some(Cow::Owned(id.to_string()))
+}
+
+/// Returns the numeric block id and data value of a block in a pre-flattening (before 1.13)
+/// chunk.
+///
+/// `x` and `z` are block coordinates within the chunk (0 - 15), and `y` is the absolute block
+/// height.  Block ids are read from a section's `Blocks` array, with the extra 4 bits from its
+/// `Add` array if it has one (for modded blocks), which is why the id is a `u16`.  Returns
+/// `None` if the chunk has no section at that height, or isn't a pre-flattening chunk.
+pub fn legacy_block_id_at(chunk: &Tag, x: u8, y: i32, z: u8) -> Option<(u16, u8)> {
+    if x >= 16 || z >= 16 {
+        return None;
+    }
+    let section = sections(chunk)?
+        .iter()
+        .find(|s| s.key("Y").as_i8().map(i32::from) == Some(y.div_euclid(16)))?;
+
+    let idx = y.rem_euclid(16) as usize * 256 + z as usize * 16 + x as usize;
+    // nibble arrays hold two values per byte, with the even index in the low bits
+    let nibble = |arr: &Vec<u8>| arr.get(idx / 2).map(|b| (b >> ((idx % 2) * 4)) & 0xf);
+
+    let mut id = *section.key("Blocks").as_bytes()?.get(idx)? as u16;
+    if let Some(add) = section.key("Add").as_bytes() {
+        id |= (nibble(add)? as u16) << 8;
+    }
+    let data = nibble(section.key("Data").as_bytes()?)?;
+    Some((id, data))
+}
+
+/// Returns the list of entities in a chunk.
+///
+/// Before 1.17 these are stored in the chunk itself under `Level/Entities`.  Since then they
+/// live in separate entity region files, whose chunks have a top-level `Entities` list.
+pub fn entities(chunk: &Tag) -> Option<&Vec<Tag>> {
+    match chunk.key("Level") {
+        Some(level) => level.key("Entities").as_list(),
+        None => chunk.key("Entities").as_list(),
+    }
+}
+
+/// Returns the position of every entity in a chunk, read from each entity's `Pos` list.
+///
+/// Entities without a valid `Pos` are skipped.  See `entities` for where entities are found.
+pub fn collect_entity_positions(chunk: &Tag) -> Vec<[f64; 3]> {
+    entities(chunk)
+        .map(|v| v.iter().filter_map(|e| e.key("Pos").as_fpos()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the UUID of an entity, which has been stored in a few different ways over time.
+///
+/// These are, from newest to oldest:
+///
+/// * `UUID`, an int array of four ints (1.16+)
+/// * `UUIDMost` and `UUIDLeast`, a pair of longs (before 1.16)
+/// * `UUID`, a compound with `M` and `L` longs (some 1.13 - 1.15 data)
+/// * `UUID`, a string like `"069a79f4-44e9-4726-a5be-fca90e38aaf5"`
+///
+/// Returns `None` if the entity has none of these.
+pub fn entity_uuid(entity: &Tag) -> Option<u128> {
+    let from_longs = |most: i64, least: i64| (most as u64 as u128) << 64 | least as u64 as u128;
+
+    match entity.key("UUID") {
+        Some(Tag::TagIntArray(v)) if v.len() == 4 => {
+            Some(v.iter().fold(0, |uuid, &i| uuid << 32 | i as u32 as u128))
+        }
+        Some(uuid @ Tag::TagCompound(_)) => {
+            Some(from_longs(uuid.key("M").as_i64()?, uuid.key("L").as_i64()?))
+        }
+        Some(Tag::TagString(s)) => {
+            let hex: String = s.chars().filter(|&c| c != '-').collect();
+            if hex.len() != 32 {
+                return None;
+            }
+            u128::from_str_radix(&hex, 16).ok()
+        }
+        Some(_) => None,
+        None => Some(from_longs(
+            entity.key("UUIDMost").as_i64()?,
+            entity.key("UUIDLeast").as_i64()?,
+        )),
+    }
+}
+
+/// Returns the list of block entities (previously called tile entities) in a chunk.
+///
+/// Before 1.18 these are stored under `Level/TileEntities`, and since then under a top-level
+/// `block_entities`.
+pub fn block_entities(chunk: &Tag) -> Option<&Vec<Tag>> {
+    if data_version(chunk).is_some_and(|v| v >= DATA_VERSION_1_18) {
+        chunk.key("block_entities").as_list()
+    } else {
+        chunk.key("Level").key("TileEntities").as_list()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn compound(entries: Vec<(&str, Tag)>) -> Tag {
+        Tag::TagCompound(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    fn block(name: &str) -> Tag {
+        compound(vec![("Name", Tag::TagString(name.to_string()))])
+    }
+
+    /// Reinterprets packed longs built up as `u64`s as the `i64`s a long array holds
+    fn signed(longs: Vec<u64>) -> Vec<i64> {
+        longs.into_iter().map(|l| l as i64).collect()
+    }
+
+    #[test]
+    fn test_non_air_block_count_legacy() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(0, 0).unwrap();
+
+        let count = non_air_block_count(&chunk).unwrap();
+        // 5 sections, the bottom of which is mostly bedrock and stone
+        assert_eq!(count, 15679);
+    }
+
+    #[test]
+    fn test_non_air_block_count_palette() {
+        // multi-entry palette, with every block but one in the first long set to air
+        let mut data = vec![0x1111_1111_1111_1111u64; 256];
+        data[0] = 0x1;
+        let mixed = compound(vec![(
+            "block_states",
+            compound(vec![
+                (
+                    "palette",
+                    Tag::TagList(vec![block("minecraft:air"), block("minecraft:stone")]),
+                ),
+                ("data", Tag::TagLongArray(signed(data))),
+            ]),
+        )]);
+        let stone = compound(vec![(
+            "block_states",
+            compound(vec![(
+                "palette",
+                Tag::TagList(vec![block("minecraft:stone")]),
+            )]),
+        )]);
+        let air = compound(vec![(
+            "block_states",
+            compound(vec![(
+                "palette",
+                Tag::TagList(vec![block("minecraft:air")]),
+            )]),
+        )]);
+        let chunk = compound(vec![("sections", Tag::TagList(vec![mixed, stone, air]))]);
+
+        assert_eq!(non_air_block_count(&chunk), Some(4096 - 15 + 4096));
+    }
+
+    #[test]
+    fn test_non_air_block_count_light_only() {
+        // 1.14 - 1.17 keep sections with only light data at Y=-1 and Y=16
+        let light = |y: i8| {
+            compound(vec![
+                ("Y", Tag::TagByte(y)),
+                ("SkyLight", Tag::TagByteArray(vec![0xff; 2048])),
+            ])
+        };
+        let stone = compound(vec![
+            ("Y", Tag::TagByte(0)),
+            ("Palette", Tag::TagList(vec![block("minecraft:stone")])),
+        ]);
+        let chunk = compound(vec![(
+            "Level",
+            compound(vec![(
+                "Sections",
+                Tag::TagList(vec![light(-1), stone, light(16)]),
+            )]),
+        )]);
+
+        assert_eq!(non_air_block_count(&chunk), Some(4096));
+    }
+
+    #[test]
+    fn test_heightmap() {
+        // 9 bits per entry, 7 entries per long
+        let heights: Vec<u64> = (0..256).map(|i| 64 + i % 100).collect();
+        let mut longs = vec![0u64; 37];
+        for (i, h) in heights.iter().enumerate() {
+            longs[i / 7] |= h << ((i % 7) * 9);
+        }
+        let chunk = compound(vec![
+            ("DataVersion", Tag::TagInt(2975)),
+            (
+                "Heightmaps",
+                compound(vec![("WORLD_SURFACE", Tag::TagLongArray(signed(longs)))]),
+            ),
+        ]);
+
+        let decoded = heightmap(&chunk, "WORLD_SURFACE").unwrap();
+        assert_eq!(decoded.len(), 256);
+        assert_eq!(decoded[0], 64);
+        assert_eq!(decoded[255], 64 + 55);
+        assert!(heightmap(&chunk, "MOTION_BLOCKING").is_none());
+    }
+
+    #[test]
+    fn test_heightmap_legacy() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        // pre-1.13 chunks only have the old HeightMap int array
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(0, 0).unwrap();
+        assert!(heightmap(&chunk, "WORLD_SURFACE").is_none());
+    }
+
+    #[test]
+    fn test_biome_at() {
+        // 2 bits per entry, so 32 entries per long; cell 0 is plains and the rest are forest
+        let mut data = vec![0x5555_5555_5555_5555u64; 2];
+        data[0] &= !0b11;
+        let biomes = |palette: Vec<&str>, data: Option<Vec<u64>>| {
+            let palette = palette
+                .into_iter()
+                .map(|b| Tag::TagString(b.to_string()))
+                .collect();
+            let mut entries = vec![("palette", Tag::TagList(palette))];
+            if let Some(data) = data {
+                entries.push(("data", Tag::TagLongArray(signed(data))));
+            }
+            compound(entries)
+        };
+        let section =
+            |y: i8, biomes: Tag| compound(vec![("Y", Tag::TagByte(y)), ("biomes", biomes)]);
+        let chunk = compound(vec![
+            ("DataVersion", Tag::TagInt(2975)),
+            (
+                "sections",
+                Tag::TagList(vec![
+                    section(-4, biomes(vec!["minecraft:deep_dark"], None)),
+                    section(
+                        4,
+                        biomes(
+                            vec!["minecraft:plains", "minecraft:forest", "minecraft:river"],
+                            Some(data),
+                        ),
+                    ),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(biome_at(&chunk, 0, 64, 0).unwrap(), "minecraft:plains");
+        assert_eq!(biome_at(&chunk, 3, 67, 3).unwrap(), "minecraft:plains");
+        assert_eq!(biome_at(&chunk, 4, 64, 0).unwrap(), "minecraft:forest");
+        assert_eq!(biome_at(&chunk, 15, 79, 15).unwrap(), "minecraft:forest");
+        assert_eq!(biome_at(&chunk, 8, -60, 8).unwrap(), "minecraft:deep_dark");
+        assert!(biome_at(&chunk, 0, 0, 0).is_none());
+        assert!(biome_at(&chunk, 16, 64, 0).is_none());
+    }
+
+    #[test]
+    fn test_biome_at_legacy() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(0, 0).unwrap();
+
+        let biomes = chunk.key("Level").key("Biomes").as_bytes().unwrap();
+        let expected = biomes[5 * 16 + 3].to_string();
+        assert_eq!(biome_at(&chunk, 3, 70, 5).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_biome_at_int_array() {
+        let chunk = |biomes: Vec<i32>| {
+            compound(vec![(
+                "Level",
+                compound(vec![("Biomes", Tag::TagIntArray(biomes))]),
+            )])
+        };
+
+        // 1.13 - 1.14: one id per column
+        let columns = chunk((0..256).collect());
+        assert_eq!(biome_at(&columns, 3, 10, 5).unwrap(), "83");
+        assert_eq!(biome_at(&columns, 3, 200, 5).unwrap(), "83");
+
+        // 1.15 - 1.17: one id per 4x4x4 cell
+        let cells = chunk((0..1024).collect());
+        assert_eq!(biome_at(&cells, 3, 10, 5).unwrap(), "36");
+        assert_eq!(biome_at(&cells, 3, 200, 5).unwrap(), "804");
+        assert!(biome_at(&cells, 3, 256, 5).is_none());
+
+        assert!(biome_at(&chunk(vec![1; 100]), 0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_legacy_block_id_at() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(0, 0).unwrap();
+
+        // the bottom of the world is bedrock
+        assert_eq!(legacy_block_id_at(&chunk, 0, 0, 0).unwrap().0, 7);
+
+        let section = chunk.key("Level").key("Sections").index(0);
+        assert_eq!(section.key("Y").as_i8(), Some(0));
+        let idx = 3 * 256 + 9 * 16 + 5;
+        let data = section.key("Data").as_bytes().unwrap()[idx / 2] >> 4;
+        assert_eq!(
+            legacy_block_id_at(&chunk, 5, 3, 9),
+            Some((section.key("Blocks").as_bytes().unwrap()[idx] as u16, data))
+        );
+        assert!(legacy_block_id_at(&chunk, 0, 255, 0).is_none());
+        assert!(legacy_block_id_at(&chunk, 16, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_legacy_block_id_add() {
+        let mut add = vec![0; 2048];
+        add[0] = 0x21;
+        let section = compound(vec![
+            ("Y", Tag::TagByte(-1)),
+            ("Blocks", Tag::TagByteArray(vec![5; 4096])),
+            ("Add", Tag::TagByteArray(add)),
+            ("Data", Tag::TagByteArray(vec![0x43; 2048])),
+        ]);
+        let chunk = compound(vec![(
+            "Level",
+            compound(vec![("Sections", Tag::TagList(vec![section]))]),
+        )]);
+
+        assert_eq!(legacy_block_id_at(&chunk, 0, -16, 0), Some((0x105, 3)));
+        assert_eq!(legacy_block_id_at(&chunk, 1, -16, 0), Some((0x205, 4)));
+        assert_eq!(legacy_block_id_at(&chunk, 2, -16, 0), Some((5, 3)));
+    }
+
+    #[test]
+    fn test_entities_old() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(1, 12).unwrap();
+
+        let entities = entities(&chunk).unwrap();
+        assert_eq!(entities.len(), 3);
+        assert_eq!(entities[0].key("id").as_string().unwrap(), "Cow");
+        assert!(block_entities(&chunk).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_collect_entity_positions() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(1, 12).unwrap();
+
+        let positions = collect_entity_positions(&chunk);
+        assert_eq!(positions.len(), 3);
+        for (pos, cow) in positions.iter().zip(entities(&chunk).unwrap()) {
+            let expected = cow.key("Pos");
+            for (i, &value) in pos.iter().enumerate() {
+                assert_eq!(Some(value), expected.index(i).as_f64());
+            }
+            // chunk (1, 12) covers blocks 16 - 31 on x, and 192 - 207 on z
+            assert!((16.0..32.0).contains(&pos[0]));
+            assert!((192.0..208.0).contains(&pos[2]));
+        }
+
+        let empty = compound(vec![]);
+        assert!(collect_entity_positions(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_entity_uuid() {
+        let expected = 0x069a_79f4_44e9_4726_a5be_fca9_0e38_aaf5_u128;
+        let most = 0x069a_79f4_44e9_4726_u64 as i64;
+        let least = 0xa5be_fca9_0e38_aaf5_u64 as i64;
+
+        let int_array = compound(vec![(
+            "UUID",
+            Tag::TagIntArray(vec![
+                0x069a79f4,
+                0x44e94726,
+                0xa5befca9_u32 as i32,
+                0x0e38aaf5,
+            ]),
+        )]);
+        let longs = compound(vec![
+            ("UUIDMost", Tag::TagLong(most)),
+            ("UUIDLeast", Tag::TagLong(least)),
+        ]);
+        let m_l = compound(vec![(
+            "UUID",
+            compound(vec![("M", Tag::TagLong(most)), ("L", Tag::TagLong(least))]),
+        )]);
+        let string = compound(vec![(
+            "UUID",
+            Tag::TagString("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string()),
+        )]);
+
+        assert_eq!(entity_uuid(&int_array), Some(expected));
+        assert_eq!(entity_uuid(&longs), Some(expected));
+        assert_eq!(entity_uuid(&m_l), Some(expected));
+        assert_eq!(entity_uuid(&string), Some(expected));
+        assert_eq!(entity_uuid(&compound(vec![])), None);
+    }
+
+    #[test]
+    fn test_entity_uuid_fixture() {
+        use crate::RegionFile;
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let chunk = region.load_chunk(1, 12).unwrap();
+
+        let cow = &entities(&chunk).unwrap()[0];
+        let uuid = entity_uuid(cow).unwrap();
+        assert_eq!(
+            (uuid >> 64) as u64 as i64,
+            cow.key("UUIDMost").as_i64().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_entities_new() {
+        let sign = compound(vec![("id", Tag::TagString("minecraft:sign".to_string()))]);
+        let chunk = compound(vec![
+            ("DataVersion", Tag::TagInt(2975)),
+            ("block_entities", Tag::TagList(vec![sign])),
+        ]);
+        let block_entities = block_entities(&chunk).unwrap();
+        assert_eq!(
+            block_entities[0].key("id").as_string().unwrap(),
+            "minecraft:sign"
+        );
+
+        // 1.17+ entities come from a separate entity region file
+        let cow = compound(vec![("id", Tag::TagString("minecraft:cow".to_string()))]);
+        let entity_chunk = compound(vec![
+            ("DataVersion", Tag::TagInt(2975)),
+            ("Entities", Tag::TagList(vec![cow])),
+        ]);
+        let entities = entities(&entity_chunk).unwrap();
+        assert_eq!(entities[0].key("id").as_string().unwrap(), "minecraft:cow");
+    }
+
+    #[test]
+    fn test_unpack_spanning() {
+        // 5 bit entries, packed across long boundaries
+        let values: Vec<u64> = (0..4096).map(|i| i % 32).collect();
+        let mut longs = vec![0u64; 4096 * 5 / 64];
+        for (i, v) in values.iter().enumerate() {
+            let bit = i * 5;
+            longs[bit / 64] |= v << (bit % 64);
+            if bit % 64 + 5 > 64 {
+                longs[bit / 64 + 1] |= v >> (64 - bit % 64);
+            }
+        }
+        assert_eq!(unpack(&signed(longs), 5, 4096).unwrap(), values);
+    }
+}
@@ -10,6 +10,12 @@ pub enum Error {
     /// There was an error during IO
     Io(io::Error),
     BadEncoding(string::FromUtf8Error),
+
+    /// A string had an unpaired UTF-16 surrogate in its modified UTF-8 encoding
+    BadModifiedUtf8 {
+        /// Offset of the bad surrogate, in bytes from the start of the string
+        offset: usize,
+    },
     /// Currently, only zlib is implemented.
     UnsupportedCompressionFormat {
         /// Compression type byte from the format.
@@ -17,8 +23,89 @@ pub enum Error {
     },
     UnexpectedEOF,
 
+    /// Compressed chunk data was corrupt and couldn't be decompressed
+    Decompression {
+        /// Compression type byte from the format
+        compression_type: u8,
+        /// The error from the decompressor
+        source: io::Error,
+    },
+
     /// An unexpected tag was found while NBT Parsing
     UnexpectedTag(u8),
+
+    /// A list claimed to hold TAG_End elements, but wasn't empty
+    InvalidList,
+
+    /// A list to be written contained elements of more than one type
+    HeterogeneousList,
+
+    /// The strings in some NBT data added up to more than `ParseOptions::max_total_string_bytes`
+    StringBudgetExceeded,
+
+    /// Lists and compounds were nested more deeply than `ParseOptions::max_depth` allows
+    DepthLimitExceeded,
+
+    /// A tag was not of the type needed for the operation
+    UnexpectedType {
+        /// Name of the expected tag type, like "TAG_Compound"
+        expected: &'static str,
+        /// Name of the tag type that was found
+        found: &'static str,
+    },
+
+    /// Loading a chunk from a region failed
+    ChunkLoad {
+        /// X coordinate of the chunk within its region
+        x: u8,
+        /// Z coordinate of the chunk within its region
+        z: u8,
+        /// What went wrong
+        source: Box<Error>,
+    },
+
+    /// A file doesn't look like a region file
+    NotARegionFile,
+
+    /// One or more chunks in a region failed to load
+    CorruptRegion(MultiError),
+}
+
+impl Error {
+    /// Returns the `io::ErrorKind` behind this error, if it was caused by IO.
+    ///
+    /// `UnexpectedEOF` is reported as `io::ErrorKind::UnexpectedEof`, a `Decompression` error
+    /// reports the kind of the decompressor's error, and a `ChunkLoad` error reports the kind
+    /// of the error it wraps.
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Error::Io(e) => Some(e.kind()),
+            Error::UnexpectedEOF => Some(io::ErrorKind::UnexpectedEof),
+            Error::Decompression { source, .. } => Some(source.kind()),
+            Error::ChunkLoad { source, .. } => source.io_kind(),
+            _ => None,
+        }
+    }
+}
+
+/// Errors from an operation over many chunks, each tagged with the chunk's coordinates
+#[derive(Debug, PartialEq)]
+pub struct MultiError {
+    /// The coordinates of each chunk that failed, and why
+    pub errors: Vec<(u8, u8, Error)>,
+}
+
+impl error::Error for MultiError {}
+
+impl fmt::Display for MultiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} chunk error(s)", self.errors.len())?;
+        for (i, (x, z, e)) in self.errors.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}({}, {}) {}", sep, x, z, e)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<string::FromUtf8Error> for Error {
@@ -27,24 +114,178 @@ impl From<string::FromUtf8Error> for Error {
     }
 }
 
+impl From<MultiError> for Error {
+    fn from(err: MultiError) -> Error {
+        Error::CorruptRegion(err)
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::Io(err)
     }
 }
 
-impl error::Error for Error {}
+impl PartialEq for Error {
+    /// IO errors are compared by their `ErrorKind`, everything else by value
+    fn eq(&self, other: &Error) -> bool {
+        match (self, other) {
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (Error::BadEncoding(a), Error::BadEncoding(b)) => a == b,
+            (Error::BadModifiedUtf8 { offset: a }, Error::BadModifiedUtf8 { offset: b }) => a == b,
+            (
+                Error::UnsupportedCompressionFormat {
+                    compression_type: a,
+                },
+                Error::UnsupportedCompressionFormat {
+                    compression_type: b,
+                },
+            ) => a == b,
+            (Error::UnexpectedEOF, Error::UnexpectedEOF) => true,
+            (
+                Error::Decompression {
+                    compression_type: a_type,
+                    source: a,
+                },
+                Error::Decompression {
+                    compression_type: b_type,
+                    source: b,
+                },
+            ) => a_type == b_type && a.kind() == b.kind(),
+            (Error::UnexpectedTag(a), Error::UnexpectedTag(b)) => a == b,
+            (Error::InvalidList, Error::InvalidList) => true,
+            (Error::HeterogeneousList, Error::HeterogeneousList) => true,
+            (Error::StringBudgetExceeded, Error::StringBudgetExceeded) => true,
+            (Error::DepthLimitExceeded, Error::DepthLimitExceeded) => true,
+            (
+                Error::UnexpectedType {
+                    expected: a_expected,
+                    found: a_found,
+                },
+                Error::UnexpectedType {
+                    expected: b_expected,
+                    found: b_found,
+                },
+            ) => a_expected == b_expected && a_found == b_found,
+            (
+                Error::ChunkLoad {
+                    x: a_x,
+                    z: a_z,
+                    source: a,
+                },
+                Error::ChunkLoad {
+                    x: b_x,
+                    z: b_z,
+                    source: b,
+                },
+            ) => a_x == b_x && a_z == b_z && a == b,
+            (Error::NotARegionFile, Error::NotARegionFile) => true,
+            (Error::CorruptRegion(a), Error::CorruptRegion(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::BadEncoding(e) => Some(e),
+            Error::Decompression { source, .. } => Some(source),
+            Error::ChunkLoad { source, .. } => Some(source.as_ref()),
+            Error::CorruptRegion(failures) => Some(failures),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
             Error::Io(..) => write!(f, "IO Error"),
             Error::BadEncoding(..) => write!(f, "Bad Encoding"),
+            Error::BadModifiedUtf8 { offset } => {
+                write!(
+                    f,
+                    "Bad Modified UTF-8 (unpaired surrogate at byte {})",
+                    offset
+                )
+            }
             Error::UnexpectedEOF => write!(f, "Unexpected EOF"),
+            Error::Decompression { .. } => write!(f, "Decompression Error"),
             Error::UnexpectedTag(..) => write!(f, "Unexpected Tag"),
+            Error::InvalidList => write!(f, "Invalid List"),
+            Error::HeterogeneousList => write!(f, "Heterogeneous List"),
+            Error::StringBudgetExceeded => write!(f, "String Budget Exceeded"),
+            Error::DepthLimitExceeded => write!(f, "Depth Limit Exceeded"),
+            Error::UnexpectedType { expected, found } => {
+                write!(
+                    f,
+                    "Unexpected Type (expected {}, found {})",
+                    expected, found
+                )
+            }
+            Error::ChunkLoad { x, z, source } => {
+                write!(f, "Error loading chunk ({}, {}): {}", x, z, source)
+            }
+            Error::NotARegionFile => write!(f, "Not a Region File"),
+            Error::CorruptRegion(failures) => {
+                write!(f, "Corrupt Region ({} bad chunks)", failures.errors.len())
+            }
             Error::UnsupportedCompressionFormat {
                 compression_type: _,
             } => write!(f, "Unsupported Compression"),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_eq() {
+        assert_eq!(Error::UnexpectedTag(5), Error::UnexpectedTag(5));
+        assert_ne!(Error::UnexpectedTag(5), Error::UnexpectedTag(9));
+        assert_ne!(Error::UnexpectedTag(5), Error::UnexpectedEOF);
+        assert_eq!(
+            Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "a")),
+            Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "b"))
+        );
+    }
+
+    #[test]
+    fn test_io_kind() {
+        let e = Error::from(io::Error::from(io::ErrorKind::WouldBlock));
+        assert_eq!(e.io_kind(), Some(io::ErrorKind::WouldBlock));
+        assert_eq!(
+            Error::UnexpectedEOF.io_kind(),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+        assert_eq!(Error::InvalidList.io_kind(), None);
+
+        let corrupt = Error::Decompression {
+            compression_type: 2,
+            source: io::ErrorKind::InvalidData.into(),
+        };
+        assert_eq!(corrupt.io_kind(), Some(io::ErrorKind::InvalidData));
+
+        let wrapped = Error::ChunkLoad {
+            x: 1,
+            z: 2,
+            source: Box::new(e),
+        };
+        assert_eq!(wrapped.io_kind(), Some(io::ErrorKind::WouldBlock));
+    }
+
+    #[test]
+    fn test_multi_error_display() {
+        let e = MultiError {
+            errors: vec![(1, 2, Error::UnexpectedEOF), (30, 4, Error::InvalidList)],
+        };
+        assert_eq!(
+            e.to_string(),
+            "2 chunk error(s): (1, 2) Unexpected EOF; (30, 4) Invalid List"
+        );
+    }
+}
@@ -1,13 +1,20 @@
-//! nbtrs is a crate for parsing (reading) NBT and McRegion files.  There is no write support.
+//! nbtrs is a crate for parsing (reading) NBT and McRegion files.  NBT tags can also be written
+//! back out with `Tag::write`.
 //!
 //! # Examples
 //!
 //! Put some here
 
+mod bytes;
+pub mod chunk;
 mod error;
+mod mutf8;
 mod nbt;
 mod region;
 
-pub use error::Error;
-pub use nbt::{Tag, Taglike};
-pub use region::RegionFile;
+pub use error::{Error, MultiError};
+pub use nbt::{
+    Compression, DebugNode, ParseOptions, PathSeg, Scalar, ScalarTag, Tag, TagCursor, TagVisitor,
+    Taglike, UnknownTagPolicy,
+};
+pub use region::{ChunkIter, RegionFile, TryClone};
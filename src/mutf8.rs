@@ -0,0 +1,119 @@
+//! Encoding and decoding of Java's "modified UTF-8", which NBT strings are actually stored in
+//!
+//! This differs from standard UTF-8 in two ways: the null character is encoded as the two
+//! bytes `C0 80`, and characters outside the Basic Multilingual Plane are encoded as a UTF-16
+//! surrogate pair, with each half encoded as its own 3 byte sequence.
+
+use std::borrow::Cow;
+
+/// Encodes a string as modified UTF-8.
+///
+/// Most strings are encoded identically in both forms, so the string's own bytes are borrowed
+/// unless it contains a null or a character outside the Basic Multilingual Plane.
+pub fn encode(s: &str) -> Cow<'_, [u8]> {
+    if !s.chars().any(|c| c == '\0' || c.len_utf8() == 4) {
+        return Cow::Borrowed(s.as_bytes());
+    }
+    let mut out = Vec::with_capacity(s.len() + 2);
+    for c in s.chars() {
+        match c {
+            '\0' => out.extend_from_slice(&[0xc0, 0x80]),
+            c if c.len_utf8() == 4 => {
+                let mut units = [0; 2];
+                for &unit in c.encode_utf16(&mut units).iter() {
+                    // each surrogate is encoded as if it were a character of its own
+                    out.push(0xe0 | (unit >> 12) as u8);
+                    out.push(0x80 | (unit >> 6 & 0x3f) as u8);
+                    out.push(0x80 | (unit & 0x3f) as u8);
+                }
+            }
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Decodes modified UTF-8 bytes into a string.
+///
+/// If the bytes contain an unpaired surrogate, the error holds the offset of the start of its
+/// encoding.  For any other invalid data, the error is `None`.
+pub fn decode(bytes: &[u8]) -> Result<String, Option<usize>> {
+    let mut s = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let (unit, len) = code_unit(&bytes[i..]).ok_or(None)?;
+        let c = match unit {
+            0xd800..=0xdbff => {
+                // a high surrogate, which must be followed by a low one
+                let (low, low_len) = match code_unit(&bytes[i + len..]) {
+                    Some((low @ 0xdc00..=0xdfff, low_len)) => (low, low_len),
+                    _ => return Err(Some(i)),
+                };
+                let c = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+                i += low_len;
+                char::from_u32(c).ok_or(None)?
+            }
+            0xdc00..=0xdfff => return Err(Some(i)),
+            _ => char::from_u32(unit).ok_or(None)?,
+        };
+        s.push(c);
+        i += len;
+    }
+    Ok(s)
+}
+
+/// Decodes one 1, 2, or 3 byte sequence from the start of `bytes`, returning the UTF-16 code
+/// unit it encodes and its length
+fn code_unit(bytes: &[u8]) -> Option<(u32, usize)> {
+    let cont = |idx: usize| match bytes.get(idx) {
+        Some(&b) if b & 0xc0 == 0x80 => Some((b & 0x3f) as u32),
+        _ => None,
+    };
+    let first = *bytes.first()?;
+    match first {
+        0x00..=0x7f => Some((first as u32, 1)),
+        0xc0..=0xdf => Some((((first & 0x1f) as u32) << 6 | cont(1)?, 2)),
+        0xe0..=0xef => Some((((first & 0x0f) as u32) << 12 | cont(1)? << 6 | cont(2)?, 3)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"hello").unwrap(), "hello");
+        assert_eq!(decode(&[b'a', 0xc0, 0x80, b'b']).unwrap(), "a\0b");
+        // U+1F600, as a surrogate pair
+        assert_eq!(
+            decode(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]).unwrap(),
+            "\u{1f600}"
+        );
+        assert_eq!(decode(&[0xe2, 0x82, 0xac]).unwrap(), "\u{20ac}");
+
+        // a 4 byte sequence isn't valid modified UTF-8
+        assert_eq!(decode("\u{1f600}".as_bytes()), Err(None));
+        assert_eq!(decode(&[b'a', 0xc3]), Err(None));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert!(matches!(encode("hello \u{20ac}"), Cow::Borrowed(_)));
+        assert_eq!(&encode("a\0b")[..], &[b'a', 0xc0, 0x80, b'b']);
+        assert_eq!(
+            &encode("\u{1f600}")[..],
+            &[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]
+        );
+    }
+
+    #[test]
+    fn test_bad_surrogates() {
+        // a high surrogate with nothing after it
+        assert_eq!(decode(&[b'a', b'b', 0xed, 0xa0, 0xbd, b'c']), Err(Some(2)));
+        assert_eq!(decode(&[b'a', 0xed, 0xa0, 0xbd]), Err(Some(1)));
+        // a low surrogate on its own
+        assert_eq!(decode(&[0xed, 0xb8, 0x80]), Err(Some(0)));
+    }
+}
@@ -1,8 +1,17 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::str::{self, FromStr};
 
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+use super::bytes::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use super::error::Error;
+use super::mutf8;
 
 /// An NBT Tag
 #[derive(Debug, PartialEq, Clone)]
@@ -18,8 +27,156 @@ pub enum Tag {
     TagString(String),
     TagList(Vec<Tag>),
     TagCompound(HashMap<String, Tag>),
-    TagIntArray(Vec<u32>),
-    TagLongArray(Vec<u64>),
+    TagIntArray(Vec<i32>),
+    TagLongArray(Vec<i64>),
+}
+
+/// How NBT data is compressed when written with `Tag::write_compressed`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    /// gzip, as used by `level.dat` and most other standalone NBT files
+    Gzip,
+    /// zlib, as used by chunks in region files
+    Zlib,
+    /// No compression at all
+    None,
+}
+
+/// Options controlling how NBT data is parsed
+///
+/// The defaults match the behavior of `Tag::parse`.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Replace invalid UTF-8 in strings with U+FFFD, rather than failing the whole parse
+    pub lossy_strings: bool,
+
+    /// What to do when a tag type that this crate doesn't know about is found
+    pub on_unknown_tag: UnknownTagPolicy,
+
+    /// Assume every element of a list has the list's declared element type, as the spec says.
+    ///
+    /// When false, each list element is instead read as a type byte followed by a payload,
+    /// which is what some non-compliant third-party tools write.
+    pub trust_list_type: bool,
+
+    /// The most string data, in bytes, that a single parse may read, counting every string
+    /// value and compound key.  `None` means no limit.
+    ///
+    /// Exceeding it fails the parse with `Error::StringBudgetExceeded`.
+    pub max_total_string_bytes: Option<usize>,
+
+    /// How deeply lists and compounds may be nested, counting the root compound as depth 1.
+    ///
+    /// Parsing recurses for each level, so this keeps maliciously deep data from overflowing
+    /// the stack.  Going deeper fails the parse with `Error::DepthLimitExceeded`.
+    pub max_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            lossy_strings: false,
+            on_unknown_tag: UnknownTagPolicy::default(),
+            trust_list_type: true,
+            max_total_string_bytes: None,
+            max_depth: 512,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// If `tag_type` is unknown and should be skipped, returns the size of its payload
+    fn skip_size(&self, tag_type: u8) -> Option<usize> {
+        match self.on_unknown_tag {
+            UnknownTagPolicy::Skip(size) if tag_type > 12 => Some(size),
+            _ => None,
+        }
+    }
+}
+
+/// The options for a single parse, along with how much of their budgets has been used so far
+struct ParseState<'o> {
+    opts: &'o ParseOptions,
+    string_bytes: Cell<usize>,
+    depth: Cell<usize>,
+}
+
+impl<'o> ParseState<'o> {
+    fn new(opts: &'o ParseOptions) -> ParseState<'o> {
+        ParseState {
+            opts,
+            string_bytes: Cell::new(0),
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Goes one level deeper into nested lists and compounds, checking `max_depth`
+    fn enter(&self) -> Result<(), Error> {
+        if self.depth.get() >= self.opts.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth.set(self.depth.get() + 1);
+        Ok(())
+    }
+
+    fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Counts a string of `len` bytes against `max_total_string_bytes`
+    fn charge_string(&self, len: usize) -> Result<(), Error> {
+        let total = self.string_bytes.get() + len;
+        match self.opts.max_total_string_bytes {
+            Some(max) if total > max => Err(Error::StringBudgetExceeded),
+            _ => {
+                self.string_bytes.set(total);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// How to handle tag types that this crate doesn't know about
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnknownTagPolicy {
+    /// Fail with `Error::UnexpectedTag`
+    #[default]
+    Error,
+    /// Skip over the tag, assuming its payload is always the given number of bytes.
+    ///
+    /// Unknown tags in a compound are left out of it, and a list of unknown tags is read as an
+    /// empty list.  An unknown tag at the root is still an error.
+    Skip(usize),
+}
+
+/// A numeric tag value, widened to 64 bits, along with the type id of the tag it came from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Scalar {
+    /// From a TagByte, TagShort, TagInt, or TagLong
+    Int { value: i64, type_id: u8 },
+    /// From a TagFloat or TagDouble
+    Float { value: f64, type_id: u8 },
+}
+
+impl Scalar {
+    /// The type id of the tag this scalar came from
+    pub fn type_id(&self) -> u8 {
+        match *self {
+            Scalar::Int { type_id, .. } | Scalar::Float { type_id, .. } => type_id,
+        }
+    }
+
+    /// Converts this scalar back into a tag of its original type
+    pub fn to_tag(&self) -> Tag {
+        match *self {
+            Scalar::Int { value, type_id: 1 } => Tag::TagByte(value as i8),
+            Scalar::Int { value, type_id: 2 } => Tag::TagShort(value as i16),
+            Scalar::Int { value, type_id: 3 } => Tag::TagInt(value as i32),
+            Scalar::Int { value, .. } => Tag::TagLong(value),
+            Scalar::Float { value, type_id: 5 } => Tag::TagFloat(value as f32),
+            Scalar::Float { value, .. } => Tag::TagDouble(value),
+        }
+    }
 }
 
 /// trait to simplify grabbing nested NBT data
@@ -77,12 +234,38 @@ pub trait Taglike<'t>: Sized {
     fn as_map(&self) -> Option<&'t HashMap<String, Tag>> {
         self.map_tag(|t| t.as_map())
     }
-    fn as_ints(&self) -> Option<&'t Vec<u32>> {
+    fn as_ints(&self) -> Option<&'t Vec<i32>> {
         self.map_tag(|t| t.as_ints())
     }
-    fn as_longs(&self) -> Option<&'t Vec<u64>> {
+    fn as_longs(&self) -> Option<&'t Vec<i64>> {
         self.map_tag(|t| t.as_longs())
     }
+    fn as_scalar(&self) -> Option<Scalar> {
+        self.map_tag(|t| {
+            let type_id = t.type_id();
+            match *t {
+                Tag::TagByte(v) => Some(Scalar::Int {
+                    value: v.into(),
+                    type_id,
+                }),
+                Tag::TagShort(v) => Some(Scalar::Int {
+                    value: v.into(),
+                    type_id,
+                }),
+                Tag::TagInt(v) => Some(Scalar::Int {
+                    value: v.into(),
+                    type_id,
+                }),
+                Tag::TagLong(value) => Some(Scalar::Int { value, type_id }),
+                Tag::TagFloat(v) => Some(Scalar::Float {
+                    value: v.into(),
+                    type_id,
+                }),
+                Tag::TagDouble(value) => Some(Scalar::Float { value, type_id }),
+                _ => None,
+            }
+        })
+    }
 
     // and now everything below this is defined in terms of the above
     fn index(self, index: usize) -> Option<&'t Tag> {
@@ -91,6 +274,78 @@ pub trait Taglike<'t>: Sized {
     fn key(self, key: &str) -> Option<&'t Tag> {
         self.as_map().and_then(|m| m.get(key))
     }
+    /// Like `key`, but ignores ASCII case differences when looking up `key`.
+    ///
+    /// An exact match is preferred if there is one.  Otherwise, this has to check every key in
+    /// the compound, so it is O(n) in the compound's size.
+    fn key_ci(self, key: &str) -> Option<&'t Tag> {
+        self.as_map().and_then(|m| {
+            m.get(key).or_else(|| {
+                m.iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(|(_, v)| v)
+            })
+        })
+    }
+    /// Runs `f` on this compound's map, so that several fields can be read with one lookup.
+    ///
+    /// Returns `None` (without running `f`) if this isn't a compound.
+    fn with_map<R, F>(self, f: F) -> Option<R>
+    where
+        F: FnOnce(&'t HashMap<String, Tag>) -> R,
+    {
+        self.as_map().map(f)
+    }
+    /// For a list of compounds, collects the value of `key` from each element.  Elements
+    /// without that key (or that aren't compounds) are skipped.
+    fn pluck(self, key: &str) -> Option<Vec<&'t Tag>> {
+        self.as_list()
+            .map(|v| v.iter().filter_map(|t| t.key(key)).collect())
+    }
+    /// Parses a string tag into some other type, like an enum of the allowed values.
+    ///
+    /// Returns `None` if this isn't a string, or if `T::from_str` fails.
+    fn as_enum<T: FromStr>(&self) -> Option<T> {
+        self.as_string().and_then(|s| s.parse().ok())
+    }
+    /// Reads a position made of three numbers, like a block position.
+    ///
+    /// This accepts a list of any numeric type, or any kind of array, with exactly three
+    /// elements.  Floating point values are rounded down.
+    fn as_ipos(&self) -> Option<[i32; 3]> {
+        self.map_tag(pos_components).map(|pos| {
+            pos.map(|s| match s {
+                Scalar::Int { value, .. } => value as i32,
+                Scalar::Float { value, .. } => value.floor() as i32,
+            })
+        })
+    }
+    /// Reads a position made of three numbers, like an entity's `Pos`.
+    ///
+    /// This accepts a list of any numeric type, or any kind of array, with exactly three
+    /// elements.
+    fn as_fpos(&self) -> Option<[f64; 3]> {
+        self.map_tag(pos_components).map(|pos| {
+            pos.map(|s| match s {
+                Scalar::Int { value, .. } => value as f64,
+                Scalar::Float { value, .. } => value,
+            })
+        })
+    }
+    /// Number of elements in a list, compound, or array.  Scalars have no length.
+    fn len(&self) -> Option<usize> {
+        self.map_tag(|t| match t {
+            Tag::TagByteArray(v) => Some(v.len()),
+            Tag::TagList(v) => Some(v.len()),
+            Tag::TagCompound(v) => Some(v.len()),
+            Tag::TagIntArray(v) => Some(v.len()),
+            Tag::TagLongArray(v) => Some(v.len()),
+            _ => None,
+        })
+    }
+    fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
 }
 
 // a helper to define as_i8, etc.
@@ -135,8 +390,8 @@ impl<'t> Taglike<'t> for &'t Tag {
     simple_getter!(ref, as_string, &'t String, Tag::TagString);
     simple_getter!(ref, as_list, &'t Vec<Tag>, Tag::TagList);
     simple_getter!(ref, as_map, &'t HashMap<String, Tag>, Tag::TagCompound);
-    simple_getter!(ref, as_ints, &'t Vec<u32>, Tag::TagIntArray);
-    simple_getter!(ref, as_longs, &'t Vec<u64>, Tag::TagLongArray);
+    simple_getter!(ref, as_ints, &'t Vec<i32>, Tag::TagIntArray);
+    simple_getter!(ref, as_longs, &'t Vec<i64>, Tag::TagLongArray);
 }
 
 // Options containing Taglike things are Taglike
@@ -169,51 +424,304 @@ where
     }
 }
 
-// now, on to actually parsing the things
+/// Reads the three numeric components of a position, from a list or an array
+fn pos_components(t: &Tag) -> Option<[Scalar; 3]> {
+    let int = |value: i64, type_id: u8| Scalar::Int { value, type_id };
+    match t {
+        Tag::TagList(v) if v.len() == 3 => Some([
+            (&v[0]).as_scalar()?,
+            (&v[1]).as_scalar()?,
+            (&v[2]).as_scalar()?,
+        ]),
+        Tag::TagByteArray(v) if v.len() == 3 => Some([0, 1, 2].map(|i| int(v[i] as i8 as i64, 1))),
+        Tag::TagIntArray(v) if v.len() == 3 => Some([0, 1, 2].map(|i| int(v[i] as i64, 3))),
+        Tag::TagLongArray(v) if v.len() == 3 => Some([0, 1, 2].map(|i| int(v[i], 4))),
+        _ => None,
+    }
+}
+
+/// Splits a path like `Data/Player/Pos[0]` into its segments, for `get_path` and friends
+fn parse_path(path: &str) -> Option<Vec<PathSeg>> {
+    let mut segs = Vec::new();
+    if path.is_empty() {
+        return Some(segs);
+    }
+    for part in path.split('/') {
+        let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !key.is_empty() || rest.is_empty() {
+            segs.push(PathSeg::Key(key.to_string()));
+        }
+        while !rest.is_empty() {
+            let end = rest.find(']')?;
+            segs.push(PathSeg::Index(rest.get(1..end)?.parse().ok()?));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return None;
+            }
+        }
+    }
+    Some(segs)
+}
+
+/// The error for a path segment applied to the wrong type of tag
+fn path_mismatch(tag: &Tag, seg: &PathSeg) -> Error {
+    Error::UnexpectedType {
+        expected: match seg {
+            PathSeg::Key(_) => "TAG_Compound",
+            PathSeg::Index(_) => "TAG_List",
+        },
+        found: tag.get_name(),
+    }
+}
+
+/// One step of a path into a tree of tags
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSeg {
+    /// A key of a compound
+    Key(String),
+    /// An index into a list
+    Index(usize),
+}
+
+/// A position within a tree of tags, for navigating it without allocating.
+///
+/// `key` and `index` return a new cursor, so lookups can be chained and intermediate cursors
+/// kept around (they're `Copy`) to run several queries from the same place.  A cursor that
+/// walked off the tree stays empty, and all of the `Taglike` getters on it return `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct TagCursor<'t> {
+    tag: Option<&'t Tag>,
+}
+
+impl<'t> TagCursor<'t> {
+    /// Creates a cursor pointing at `tag`
+    pub fn new(tag: &'t Tag) -> TagCursor<'t> {
+        TagCursor { tag: Some(tag) }
+    }
+
+    /// Moves to a key of a compound
+    pub fn key(self, key: &str) -> TagCursor<'t> {
+        TagCursor {
+            tag: self.tag.key(key),
+        }
+    }
+
+    /// Moves to an element of a list
+    pub fn index(self, index: usize) -> TagCursor<'t> {
+        TagCursor {
+            tag: self.tag.index(index),
+        }
+    }
+
+    /// The tag this cursor points at, if any
+    pub fn tag(self) -> Option<&'t Tag> {
+        self.tag
+    }
+}
+
+impl<'t> Taglike<'t> for TagCursor<'t> {
+    fn map_tag<F, T>(&self, f: F) -> Option<T>
+    where
+        F: FnOnce(&'t Tag) -> Option<T>,
+    {
+        self.tag.and_then(f)
+    }
+}
+
+// now, on to actually parsing (and writing) the things
 impl Tag {
     /// Attempts to parse some data as a NBT
+    ///
+    /// The root tag's name is decoded lossily, so invalid UTF-8 in it doesn't stop the rest
+    /// of the data from being parsed.
     pub fn parse<R>(r: &mut R) -> Result<(String, Tag), Error>
     where
         R: Read,
     {
+        Tag::read_named::<BigEndian, R>(r, &ParseOptions::default())
+    }
+
+    /// Attempts to parse some data as a NBT, using the given options
+    pub fn parse_with_options<R>(r: &mut R, opts: &ParseOptions) -> Result<(String, Tag), Error>
+    where
+        R: Read,
+    {
+        Tag::read_named::<BigEndian, R>(r, opts)
+    }
+
+    /// Attempts to parse some data as a NBT, allowing lists and compounds to be nested at most
+    /// `max_depth` deep.
+    ///
+    /// `Tag::parse` allows a depth of 512, which is far more than the game itself writes.
+    pub fn parse_with_limits<R>(r: &mut R, max_depth: usize) -> Result<(String, Tag), Error>
+    where
+        R: Read,
+    {
+        let opts = ParseOptions {
+            max_depth,
+            ..Default::default()
+        };
+        Tag::read_named::<BigEndian, R>(r, &opts)
+    }
+
+    /// Walks through some NBT data without building a tree of tags, calling `visitor` for
+    /// each tag as it's read.
+    ///
+    /// The buffers used for names and values are reused from one tag to the next, so the
+    /// number of allocations doesn't grow with the size of the data (strings that aren't valid
+    /// UTF-8 are the exception).  If any visitor method returns `ControlFlow::Break`, parsing
+    /// stops right away, leaving the reader partway through the data.
+    pub fn parse_streaming<R, V>(r: &mut R, visitor: &mut V) -> Result<(), Error>
+    where
+        R: Read,
+        V: TagVisitor,
+    {
+        let opts = ParseOptions::default();
         let ty = r.read_u8()?;
-        let name = Tag::read_string(r)?;
-        let tag = Tag::parse_tag(r, Some(ty))?;
-        Ok((name, tag))
+        // the root name is decoded lossily, just like in `Tag::parse`
+        let name_opts = ParseOptions {
+            lossy_strings: true,
+            ..opts.clone()
+        };
+        let name = Tag::read_string::<BigEndian, R>(r, &ParseState::new(&name_opts))?;
+        let mut streamer = Streamer {
+            r,
+            visitor,
+            st: ParseState::new(&opts),
+            keys: Vec::new(),
+            text: Vec::new(),
+            ints: Vec::new(),
+            longs: Vec::new(),
+        };
+        // the visitor already knows whether it stopped early
+        streamer.walk(ty, Some(&name)).map(|_| ())
+    }
+
+    /// Attempts to parse some NBT data that may be compressed.
+    ///
+    /// The first bytes are used to tell gzip data (like `level.dat`), zlib data, and
+    /// uncompressed NBT with a root compound apart.  Anything else is an
+    /// `Error::UnsupportedCompressionFormat`, with the first byte as its `compression_type`.
+    pub fn parse_compressed<R>(r: &mut R) -> Result<(String, Tag), Error>
+    where
+        R: Read,
+    {
+        let mut magic = [0; 2];
+        r.read_exact(&mut magic)?;
+        let mut r = (&magic[..]).chain(r);
+        match magic {
+            [0x1f, 0x8b] => Tag::parse(&mut GzDecoder::new(r)),
+            [0x78, _] => Tag::parse(&mut ZlibDecoder::new(r)),
+            [0x0a, _] => Tag::parse(&mut r),
+            [b, _] => Err(Error::UnsupportedCompressionFormat {
+                compression_type: b,
+            }),
+        }
+    }
+
+    /// Attempts to parse some data as a little-endian NBT, as used by Bedrock Edition
+    pub fn parse_le<R>(r: &mut R) -> Result<(String, Tag), Error>
+    where
+        R: Read,
+    {
+        Tag::read_named::<LittleEndian, R>(r, &ParseOptions::default())
     }
 
     pub fn parse_tag<R>(r: &mut R, tag_type: Option<u8>) -> Result<Tag, Error>
     where
         R: Read,
+    {
+        let opts = ParseOptions::default();
+        Tag::read_tag::<BigEndian, R>(r, tag_type, &ParseState::new(&opts))
+    }
+
+    fn read_named<B, R>(r: &mut R, opts: &ParseOptions) -> Result<(String, Tag), Error>
+    where
+        B: ByteOrder,
+        R: Read,
+    {
+        let ty = r.read_u8()?;
+        // the root name is rarely used, so a badly encoded one shouldn't fail the whole parse
+        let name_opts = ParseOptions {
+            lossy_strings: true,
+            ..opts.clone()
+        };
+        let name_st = ParseState::new(&name_opts);
+        let name = Tag::read_string::<B, R>(r, &name_st)?;
+        let st = ParseState {
+            opts,
+            string_bytes: name_st.string_bytes,
+            depth: Cell::new(0),
+        };
+        let tag = Tag::read_tag::<B, R>(r, Some(ty), &st)?;
+        Ok((name, tag))
+    }
+
+    fn read_tag<B, R>(r: &mut R, tag_type: Option<u8>, st: &ParseState) -> Result<Tag, Error>
+    where
+        B: ByteOrder,
+        R: Read,
     {
         let tag_type = tag_type.map_or_else(|| r.read_u8(), Ok)?;
+        let nested = tag_type == 9 || tag_type == 10;
+        if nested {
+            st.enter()?;
+        }
+        let tag = Tag::read_payload::<B, R>(r, tag_type, st);
+        if nested {
+            st.leave();
+        }
+        tag
+    }
+
+    fn read_payload<B, R>(r: &mut R, tag_type: u8, st: &ParseState) -> Result<Tag, Error>
+    where
+        B: ByteOrder,
+        R: Read,
+    {
         Ok(match tag_type {
             0 => Tag::TagEnd,
             1 => Tag::TagByte(r.read_i8()?),
-            2 => Tag::TagShort(r.read_i16::<BigEndian>()?),
-            3 => Tag::TagInt(r.read_i32::<BigEndian>()?),
-            4 => Tag::TagLong(r.read_i64::<BigEndian>()?),
-            5 => Tag::TagFloat(r.read_f32::<BigEndian>()?),
-            6 => Tag::TagDouble(r.read_f64::<BigEndian>()?),
+            2 => Tag::TagShort(r.read_i16::<B>()?),
+            3 => Tag::TagInt(r.read_i32::<B>()?),
+            4 => Tag::TagLong(r.read_i64::<B>()?),
+            5 => Tag::TagFloat(r.read_f32::<B>()?),
+            6 => Tag::TagDouble(r.read_f64::<B>()?),
             7 => {
                 // TAG_Byte_Array
-                let len = r.read_u32::<BigEndian>()?;
-                let mut buf = vec![0; len as usize];
-                r.read_exact(&mut buf)?;
+                let len = r.read_u32::<B>()?;
+                let mut buf = Vec::new();
+                fill(r, &mut buf, len as usize)?;
                 Tag::TagByteArray(buf)
             }
             8 => {
                 // TAG_String
-                let s = Tag::read_string(r)?;
+                let s = Tag::read_string::<B, R>(r, st)?;
                 Tag::TagString(s)
             }
             9 => {
                 // TAG_List
                 let ty = r.read_u8()?;
-                let len = r.read_u32::<BigEndian>()?;
-                let mut v = Vec::with_capacity(len as usize);
+                let len = r.read_u32::<B>()?;
+                if !st.opts.trust_list_type {
+                    // the declared type can't be relied on, so every element carries its own
+                    let mut v = Vec::with_capacity((len as usize).min(MAX_PREALLOC));
+                    for _ in 0..len {
+                        v.push(Tag::read_tag::<B, R>(r, None, st)?);
+                    }
+                    return Ok(Tag::TagList(v));
+                }
+                if ty == 0 && len > 0 {
+                    // TAG_End as the element type is only valid for empty lists
+                    return Err(Error::InvalidList);
+                }
+                if let Some(size) = st.opts.skip_size(ty) {
+                    Tag::skip(r, size * len as usize)?;
+                    return Ok(Tag::TagList(Vec::new()));
+                }
+                let mut v = Vec::with_capacity((len as usize).min(MAX_PREALLOC));
                 for _ in 0..len {
-                    let t = Tag::parse_tag(r, Some(ty))?;
+                    let t = Tag::read_tag::<B, R>(r, Some(ty), st)?;
                     v.push(t)
                 }
                 Tag::TagList(v)
@@ -226,28 +734,32 @@ impl Tag {
                     if ty == 0 {
                         break;
                     }
-                    let name = Tag::read_string(r)?;
-                    let value = Tag::parse_tag(r, Some(ty))?;
+                    let name = Tag::read_string::<B, R>(r, st)?;
+                    if let Some(size) = st.opts.skip_size(ty) {
+                        Tag::skip(r, size)?;
+                        continue;
+                    }
+                    let value = Tag::read_tag::<B, R>(r, Some(ty), st)?;
                     v.insert(name, value);
                 }
                 Tag::TagCompound(v)
             }
             11 => {
                 // TAG_IntArray
-                let len = r.read_u32::<BigEndian>()?;
-                let mut v = Vec::with_capacity(len as usize);
+                let len = r.read_u32::<B>()?;
+                let mut v = Vec::with_capacity((len as usize).min(MAX_PREALLOC));
                 for _ in 0..len {
-                    let i = r.read_u32::<BigEndian>()?;
+                    let i = r.read_i32::<B>()?;
                     v.push(i)
                 }
                 Tag::TagIntArray(v)
             }
             12 => {
                 // TAG_LongArray
-                let len = r.read_u32::<BigEndian>()?;
-                let mut v = Vec::with_capacity(len as usize);
+                let len = r.read_u32::<B>()?;
+                let mut v = Vec::with_capacity((len as usize).min(MAX_PREALLOC));
                 for _ in 0..len {
-                    let i = r.read_u64::<BigEndian>()?;
+                    let i = r.read_i64::<B>()?;
                     v.push(i)
                 }
                 Tag::TagLongArray(v)
@@ -257,14 +769,309 @@ impl Tag {
         })
     }
 
-    fn read_string<R>(r: &mut R) -> Result<String, Error>
+    /// Reads just the `DataVersion` of an NBT compound, like a chunk.
+    ///
+    /// The root compound is streamed, skipping over everything else without parsing it, and
+    /// the value is returned as soon as it's found.  Returns `None` if the root isn't a
+    /// compound, or has no `DataVersion` int.  The reader is left just after the value (or at
+    /// the end of the NBT data if it wasn't found).
+    pub fn peek_data_version<R>(r: &mut R) -> Result<Option<i32>, Error>
+    where
+        R: Read,
+    {
+        let opts = ParseOptions::default();
+        let st = ParseState::new(&opts);
+        let ty = r.read_u8()?;
+        if ty != 10 {
+            return Ok(None);
+        }
+        Tag::skip_string::<BigEndian, R>(r)?;
+        st.enter()?;
+        loop {
+            let ty = r.read_u8()?;
+            if ty == 0 {
+                return Ok(None);
+            }
+            let name = Tag::read_string::<BigEndian, R>(r, &st)?;
+            if ty == 3 && name == "DataVersion" {
+                return Ok(Some(r.read_i32::<BigEndian>()?));
+            }
+            Tag::skip_payload::<BigEndian, R>(r, ty, &st)?;
+        }
+    }
+
+    /// Reads and discards the payload of a tag of the given type
+    fn skip_payload<B, R>(r: &mut R, tag_type: u8, st: &ParseState) -> Result<(), Error>
+    where
+        B: ByteOrder,
+        R: Read,
+    {
+        match tag_type {
+            0 => {}
+            1 => Tag::skip(r, 1)?,
+            2 => Tag::skip(r, 2)?,
+            3 | 5 => Tag::skip(r, 4)?,
+            4 | 6 => Tag::skip(r, 8)?,
+            7 => {
+                let len = r.read_u32::<B>()?;
+                Tag::skip(r, len as usize)?;
+            }
+            8 => Tag::skip_string::<B, R>(r)?,
+            9 => {
+                let ty = r.read_u8()?;
+                let len = r.read_u32::<B>()?;
+                if ty == 0 && len > 0 {
+                    // TAG_End as the element type is only valid for empty lists
+                    return Err(Error::InvalidList);
+                }
+                st.enter()?;
+                for _ in 0..len {
+                    Tag::skip_payload::<B, R>(r, ty, st)?;
+                }
+                st.leave();
+            }
+            10 => {
+                st.enter()?;
+                loop {
+                    let ty = r.read_u8()?;
+                    if ty == 0 {
+                        break;
+                    }
+                    Tag::skip_string::<B, R>(r)?;
+                    Tag::skip_payload::<B, R>(r, ty, st)?;
+                }
+                st.leave();
+            }
+            11 => {
+                let len = r.read_u32::<B>()?;
+                Tag::skip(r, len as usize * 4)?;
+            }
+            12 => {
+                let len = r.read_u32::<B>()?;
+                Tag::skip(r, len as usize * 8)?;
+            }
+            x => return Err(Error::UnexpectedTag(x)),
+        }
+        Ok(())
+    }
+
+    /// Reads and discards a string
+    fn skip_string<B, R>(r: &mut R) -> Result<(), Error>
+    where
+        B: ByteOrder,
+        R: Read,
+    {
+        let len = r.read_u16::<B>()?;
+        Tag::skip(r, len as usize)
+    }
+
+    /// Reads and discards exactly `len` bytes
+    fn skip<R>(r: &mut R, len: usize) -> Result<(), Error>
+    where
+        R: Read,
+    {
+        let skipped = io::copy(&mut r.take(len as u64), &mut io::sink())?;
+        if skipped < len as u64 {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(())
+    }
+
+    fn read_string<B, R>(r: &mut R, st: &ParseState) -> Result<String, Error>
     where
+        B: ByteOrder,
         R: Read,
     {
-        let len = r.read_u16::<BigEndian>()?;
+        let len = r.read_u16::<B>()?;
+        st.charge_string(len as usize)?;
         let mut buf = vec![0; len as usize];
         r.read_exact(&mut buf)?;
-        Ok(String::from_utf8(buf)?)
+        Tag::decode_string(buf, st)
+    }
+
+    /// Decodes a string that's already been read, borrowing it if it's valid UTF-8
+    fn decode_str<'b>(bytes: &'b [u8], st: &ParseState) -> Result<Cow<'b, str>, Error> {
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(Cow::Borrowed(s)),
+            Err(_) => Tag::decode_string(bytes.to_vec(), st).map(Cow::Owned),
+        }
+    }
+
+    fn decode_string(buf: Vec<u8>, st: &ParseState) -> Result<String, Error> {
+        let err = match String::from_utf8(buf) {
+            Ok(s) => return Ok(s),
+            Err(err) => err,
+        };
+
+        // strings are really in Java's modified UTF-8, which mostly matches UTF-8 but not always
+        match mutf8::decode(err.as_bytes()) {
+            Ok(s) => Ok(s),
+            Err(_) if st.opts.lossy_strings => {
+                Ok(String::from_utf8_lossy(err.as_bytes()).into_owned())
+            }
+            Err(Some(offset)) => Err(Error::BadModifiedUtf8 { offset }),
+            Err(None) => Err(err.into()),
+        }
+    }
+
+    /// Writes this tag as a named NBT
+    pub fn write<W>(&self, w: &mut W, name: &str) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        self.write_named::<BigEndian, W>(w, name)
+    }
+
+    /// Writes this tag as a named NBT, compressed with `format`
+    pub fn write_compressed<W>(
+        &self,
+        w: &mut W,
+        name: &str,
+        format: Compression,
+    ) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        match format {
+            Compression::Gzip => {
+                let mut e = GzEncoder::new(w, flate2::Compression::default());
+                self.write(&mut e, name)?;
+                e.finish()?;
+            }
+            Compression::Zlib => {
+                let mut e = ZlibEncoder::new(w, flate2::Compression::default());
+                self.write(&mut e, name)?;
+                e.finish()?;
+            }
+            Compression::None => self.write(w, name)?,
+        }
+        Ok(())
+    }
+
+    /// Checks whether writing this tag (as a named NBT) produces exactly `original`.
+    ///
+    /// This is useful for verifying that a parsed tag round-trips.  Note that compounds with
+    /// more than one entry aren't written in a predictable order, so they won't usually match.
+    pub fn bytes_match(&self, name: &str, original: &[u8]) -> bool {
+        let mut buf = Vec::with_capacity(original.len());
+        self.write(&mut buf, name).is_ok() && buf == original
+    }
+
+    /// Writes this tag as a named little-endian NBT, as used by Bedrock Edition
+    pub fn write_le<W>(&self, w: &mut W, name: &str) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        self.write_named::<LittleEndian, W>(w, name)
+    }
+
+    fn write_named<B, W>(&self, w: &mut W, name: &str) -> Result<(), Error>
+    where
+        B: ByteOrder,
+        W: Write,
+    {
+        w.write_u8(self.type_id())?;
+        Tag::write_string::<B, W>(w, name)?;
+        self.write_payload::<B, W>(w)
+    }
+
+    fn write_payload<B, W>(&self, w: &mut W) -> Result<(), Error>
+    where
+        B: ByteOrder,
+        W: Write,
+    {
+        match self {
+            Tag::TagEnd => {}
+            Tag::TagByte(v) => w.write_i8(*v)?,
+            Tag::TagShort(v) => w.write_i16::<B>(*v)?,
+            Tag::TagInt(v) => w.write_i32::<B>(*v)?,
+            Tag::TagLong(v) => w.write_i64::<B>(*v)?,
+            Tag::TagFloat(v) => w.write_f32::<B>(*v)?,
+            Tag::TagDouble(v) => w.write_f64::<B>(*v)?,
+            Tag::TagByteArray(data) => {
+                w.write_u32::<B>(data.len() as u32)?;
+                w.write_all(data)?;
+            }
+            Tag::TagString(s) => Tag::write_string::<B, W>(w, s)?,
+            Tag::TagList(data) => {
+                // an empty list is written with an element type of TAG_End
+                let ty = data.first().map_or(0, Tag::type_id);
+                if data.iter().any(|item| item.type_id() != ty) {
+                    return Err(Error::HeterogeneousList);
+                }
+                if ty == 0 && !data.is_empty() {
+                    // readers reject a TAG_End element type on a non-empty list
+                    return Err(Error::InvalidList);
+                }
+                w.write_u8(ty)?;
+                w.write_u32::<B>(data.len() as u32)?;
+                for item in data {
+                    item.write_payload::<B, W>(w)?;
+                }
+            }
+            Tag::TagCompound(map) => {
+                for (name, val) in map {
+                    if let Tag::TagEnd = val {
+                        // its type byte would be read as the end of the compound
+                        return Err(Error::UnexpectedType {
+                            expected: "a tag other than TAG_End",
+                            found: val.get_name(),
+                        });
+                    }
+                    val.write_named::<B, W>(w, name)?;
+                }
+                w.write_u8(0)?;
+            }
+            Tag::TagIntArray(data) => {
+                w.write_u32::<B>(data.len() as u32)?;
+                for i in data {
+                    w.write_i32::<B>(*i)?;
+                }
+            }
+            Tag::TagLongArray(data) => {
+                w.write_u32::<B>(data.len() as u32)?;
+                for i in data {
+                    w.write_i64::<B>(*i)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_string<B, W>(w: &mut W, s: &str) -> Result<(), Error>
+    where
+        B: ByteOrder,
+        W: Write,
+    {
+        let bytes = mutf8::encode(s);
+        if bytes.len() > u16::MAX as usize {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "string is too long for NBT",
+            )));
+        }
+        w.write_u16::<B>(bytes.len() as u16)?;
+        w.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// The type id used for this tag in the binary format
+    pub fn type_id(&self) -> u8 {
+        match self {
+            Tag::TagEnd => 0,
+            Tag::TagByte(_) => 1,
+            Tag::TagShort(_) => 2,
+            Tag::TagInt(_) => 3,
+            Tag::TagLong(_) => 4,
+            Tag::TagFloat(_) => 5,
+            Tag::TagDouble(_) => 6,
+            Tag::TagByteArray(_) => 7,
+            Tag::TagString(_) => 8,
+            Tag::TagList(_) => 9,
+            Tag::TagCompound(_) => 10,
+            Tag::TagIntArray(_) => 11,
+            Tag::TagLongArray(_) => 12,
+        }
     }
 
     pub fn get_name(&self) -> &'static str {
@@ -285,28 +1092,431 @@ impl Tag {
         }
     }
 
-    pub fn pretty_print(&self, indent: usize, name: Option<&str>) {
-        let name_s = name.map_or("".to_string(), |s| format!("(\"{}\")", s));
-
+    /// Looks up `key` in a compound, returning the whole compound along with the value.
+    ///
+    /// Returns `None` if this isn't a compound or doesn't contain `key`.  This is handy when
+    /// the value needs to be cross-referenced with its siblings.
+    pub fn map_and_get<'a>(&'a self, key: &str) -> Option<(&'a HashMap<String, Tag>, &'a Tag)> {
         match self {
-            Tag::TagCompound(ref v) => {
-                println!(
+            Tag::TagCompound(map) => map.get(key).map(|value| (map, value)),
+            _ => None,
+        }
+    }
+
+    /// Sets a nested value, given a path in the same form as `get_path`, like `Data/Pos[0]`.
+    ///
+    /// Compounds missing along the way are created, but list elements must already exist.  If
+    /// this tag, or anything along the path, exists but is the wrong type for its segment,
+    /// `Error::UnexpectedType` is returned.  A malformed path, or a list index that's out of
+    /// bounds, is an `InvalidInput` IO error.
+    pub fn set_path(&mut self, path: &str, value: Tag) -> Result<(), Error> {
+        let invalid = |msg| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        let mut segments = parse_path(path).ok_or_else(|| invalid("malformed path"))?;
+        let last = segments.pop().ok_or_else(|| invalid("empty path"))?;
+
+        let mut tag = self;
+        for seg in segments {
+            tag = match (tag, seg) {
+                (Tag::TagCompound(map), PathSeg::Key(key)) => map
+                    .entry(key)
+                    .or_insert_with(|| Tag::TagCompound(HashMap::new())),
+                (Tag::TagList(v), PathSeg::Index(idx)) => v
+                    .get_mut(idx)
+                    .ok_or_else(|| invalid("list index out of bounds"))?,
+                (other, seg) => return Err(path_mismatch(other, &seg)),
+            };
+        }
+
+        match (tag, last) {
+            (Tag::TagCompound(map), PathSeg::Key(key)) => {
+                map.insert(key, value);
+                Ok(())
+            }
+            (Tag::TagList(v), PathSeg::Index(idx)) => {
+                let slot = v
+                    .get_mut(idx)
+                    .ok_or_else(|| invalid("list index out of bounds"))?;
+                *slot = value;
+                Ok(())
+            }
+            (other, seg) => Err(path_mismatch(other, &seg)),
+        }
+    }
+
+    /// Appends `tag` to a list.
+    ///
+    /// Every element of a list must have the same type, so if the list isn't empty and `tag`
+    /// is of a different type than its elements, `Error::UnexpectedType` is returned.  This is
+    /// also returned if this tag isn't a list.
+    pub fn list_push(&mut self, tag: Tag) -> Result<(), Error> {
+        match self {
+            Tag::TagList(v) => {
+                if let Some(first) = v.first() {
+                    if first.type_id() != tag.type_id() {
+                        return Err(Error::UnexpectedType {
+                            expected: first.get_name(),
+                            found: tag.get_name(),
+                        });
+                    }
+                }
+                v.push(tag);
+                Ok(())
+            }
+            other => Err(Error::UnexpectedType {
+                expected: "TAG_List",
+                found: other.get_name(),
+            }),
+        }
+    }
+
+    /// Returns a mutable reference to an element of a list, or `None` if this isn't a list or
+    /// `index` is out of bounds.
+    pub fn list_get_mut(&mut self, index: usize) -> Option<&mut Tag> {
+        match self {
+            Tag::TagList(v) => v.get_mut(index),
+            _ => None,
+        }
+    }
+
+    /// Removes a nested value, given a path in the same form as `get_path`, returning it.
+    ///
+    /// If anything along the path doesn't exist (or the path is malformed), nothing is removed
+    /// and `None` is returned.
+    pub fn remove_path(&mut self, path: &str) -> Option<Tag> {
+        let mut segments = parse_path(path)?;
+        let last = segments.pop()?;
+
+        match (self.get_path_mut(&segments)?, last) {
+            (Tag::TagCompound(map), PathSeg::Key(key)) => map.remove(&key),
+            (Tag::TagList(v), PathSeg::Index(idx)) if idx < v.len() => Some(v.remove(idx)),
+            _ => None,
+        }
+    }
+
+    /// Looks up a nested tag, given a `/`-separated path like `Data/Player/Pos[0]`.
+    ///
+    /// Each segment is a compound key, optionally followed by one or more list indexes in
+    /// brackets.  A segment can also be just indexes, like `[2]`, to index into a list
+    /// directly.  Returns `None` if anything along the path doesn't exist, is the wrong type of
+    /// tag, or if the path is malformed.  An empty path refers to this tag.
+    pub fn get_path(&self, path: &str) -> Option<&Tag> {
+        let mut tag = self;
+        for seg in parse_path(path)? {
+            tag = match (tag, seg) {
+                (Tag::TagCompound(map), PathSeg::Key(key)) => map.get(&key)?,
+                (Tag::TagList(v), PathSeg::Index(idx)) => v.get(idx)?,
+                _ => return None,
+            };
+        }
+        Some(tag)
+    }
+
+    /// Returns a mutable reference to an existing nested tag.
+    ///
+    /// Returns `None` if anything along the path doesn't exist, or if a segment doesn't match
+    /// the type of tag it's applied to (a `Key` for a compound, an `Index` for a list).  An
+    /// empty path refers to this tag.
+    pub fn get_path_mut(&mut self, path: &[PathSeg]) -> Option<&mut Tag> {
+        let mut tag = self;
+        for seg in path {
+            tag = match (tag, seg) {
+                (Tag::TagCompound(map), PathSeg::Key(key)) => map.get_mut(key)?,
+                (Tag::TagList(v), PathSeg::Index(idx)) => v.get_mut(*idx)?,
+                _ => return None,
+            };
+        }
+        Some(tag)
+    }
+
+    /// Calls `f` on this tag and everything nested inside it, along with the path to each.
+    ///
+    /// Tags are visited in pre-order, so `f` sees a compound or list before its contents, and
+    /// any changes it makes to one are reflected in what gets visited next.  This tag itself
+    /// has an empty path.
+    pub fn visit_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&[PathSeg], &mut Tag),
+    {
+        self.visit_mut_at(&mut Vec::new(), f);
+    }
+
+    fn visit_mut_at<F>(&mut self, path: &mut Vec<PathSeg>, f: &mut F)
+    where
+        F: FnMut(&[PathSeg], &mut Tag),
+    {
+        f(path, self);
+        match self {
+            Tag::TagCompound(map) => {
+                for (key, value) in map.iter_mut() {
+                    path.push(PathSeg::Key(key.clone()));
+                    value.visit_mut_at(path, f);
+                    path.pop();
+                }
+            }
+            Tag::TagList(v) => {
+                for (idx, item) in v.iter_mut().enumerate() {
+                    path.push(PathSeg::Index(idx));
+                    item.visit_mut_at(path, f);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sorts a list of compounds by the value each element has under `key`.
+    ///
+    /// Numeric values sort before strings, and elements that are missing the key (or aren't
+    /// compounds) sort last.  The sort is stable.  Does nothing if this isn't a list.
+    pub fn sort_list_by_key(&mut self, key: &str) {
+        if let Tag::TagList(v) = self {
+            v.sort_by(|a, b| match (a.key(key), b.key(key)) {
+                (Some(a), Some(b)) => Tag::compare_values(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+    }
+
+    /// Adds `default_namespace` to any un-namespaced block or item ids, recursively.
+    ///
+    /// Every string under a key named `id` or `Name` that doesn't contain a `:` gets
+    /// `default_namespace:` prepended, so that legacy ids like `stone` can be compared with
+    /// modern ones like `minecraft:stone`.
+    pub fn normalize_ids(&mut self, default_namespace: &str) {
+        match self {
+            Tag::TagCompound(map) => {
+                for (key, value) in map.iter_mut() {
+                    match value {
+                        Tag::TagString(s) if key == "id" || key == "Name" => {
+                            if !s.contains(':') {
+                                *s = format!("{}:{}", default_namespace, s);
+                            }
+                        }
+                        _ => value.normalize_ids(default_namespace),
+                    }
+                }
+            }
+            Tag::TagList(v) => {
+                for item in v {
+                    item.normalize_ids(default_namespace);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Shortens every array and list longer than `max_len` to `max_len` elements, recursively.
+    ///
+    /// This is useful for making a lightweight preview of a large tag, like a chunk.
+    pub fn truncate_arrays(&mut self, max_len: usize) {
+        match self {
+            Tag::TagByteArray(v) => v.truncate(max_len),
+            Tag::TagIntArray(v) => v.truncate(max_len),
+            Tag::TagLongArray(v) => v.truncate(max_len),
+            Tag::TagList(v) => {
+                v.truncate(max_len);
+                for item in v {
+                    item.truncate_arrays(max_len);
+                }
+            }
+            Tag::TagCompound(map) => {
+                for value in map.values_mut() {
+                    value.truncate_arrays(max_len);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns every string value in this tag, recursively.
+    pub fn collect_strings(&self) -> Vec<&str> {
+        self.collect_strings_by(|_| true)
+    }
+
+    /// Returns every string value in this tag whose enclosing compound key matches `pred`,
+    /// recursively.
+    ///
+    /// Strings in a list are matched by the key of the list.  A string that isn't inside any
+    /// compound (like a bare `TagString`) is always included.
+    pub fn collect_strings_by<F>(&self, pred: F) -> Vec<&str>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut v = Vec::new();
+        self.collect_strings_into(None, &pred, &mut v);
+        v
+    }
+
+    fn collect_strings_into<'a, F>(&'a self, key: Option<&str>, pred: &F, out: &mut Vec<&'a str>)
+    where
+        F: Fn(&str) -> bool,
+    {
+        match self {
+            Tag::TagString(s) if key.is_none_or(pred) => out.push(s),
+            Tag::TagList(v) => {
+                for item in v {
+                    item.collect_strings_into(key, pred, out);
+                }
+            }
+            Tag::TagCompound(map) => {
+                for (k, value) in map {
+                    value.collect_strings_into(Some(k), pred, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Flattens this tag into `(path, leaf)` pairs, where `path` is made of dotted compound
+    /// keys and list indices, like `Player.Pos.0`.
+    ///
+    /// Everything that isn't a compound or a list is a leaf, including arrays.  If this tag is
+    /// itself a leaf, its path is empty.
+    pub fn flatten(&self) -> Vec<(String, &Tag)> {
+        let mut v = Vec::new();
+        self.flatten_into(String::new(), &mut v);
+        v
+    }
+
+    fn flatten_into<'a>(&'a self, path: String, out: &mut Vec<(String, &'a Tag)>) {
+        let join = |seg: &str| {
+            if path.is_empty() {
+                seg.to_string()
+            } else {
+                format!("{}.{}", path, seg)
+            }
+        };
+        match self {
+            Tag::TagCompound(map) => {
+                for (key, value) in map {
+                    value.flatten_into(join(key), out);
+                }
+            }
+            Tag::TagList(v) => {
+                for (idx, item) in v.iter().enumerate() {
+                    item.flatten_into(join(&idx.to_string()), out);
+                }
+            }
+            _ => out.push((path, self)),
+        }
+    }
+
+    /// Orders two tags by value, for sorting.  Numbers come first, then strings, then
+    /// everything else.
+    fn compare_values(a: &Tag, b: &Tag) -> Ordering {
+        let as_f64 = |s: Scalar| match s {
+            Scalar::Int { value, .. } => value as f64,
+            Scalar::Float { value, .. } => value,
+        };
+        match (a.as_scalar(), b.as_scalar()) {
+            (Some(Scalar::Int { value: a, .. }), Some(Scalar::Int { value: b, .. })) => a.cmp(&b),
+            (Some(a), Some(b)) => as_f64(a).partial_cmp(&as_f64(b)).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => match (a.as_string(), b.as_string()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        }
+    }
+
+    /// Describes the shape of this tag and everything in it, for tools to display.
+    ///
+    /// Strings are cut off after 64 characters and arrays after 8 values, like
+    /// `pretty_print`.
+    pub fn to_debug_tree(&self) -> DebugNode {
+        self.debug_node(None)
+    }
+
+    fn debug_node(&self, name: Option<&str>) -> DebugNode {
+        let mut children = Vec::new();
+        let value_summary = match self {
+            Tag::TagCompound(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                children = entries
+                    .into_iter()
+                    .map(|(k, v)| v.debug_node(Some(k)))
+                    .collect();
+                format!("{} entries", map.len())
+            }
+            Tag::TagList(v) => {
+                children = v.iter().map(|t| t.debug_node(None)).collect();
+                let ty = v.first().map_or("TAG_End", Tag::get_name);
+                format!("{} entries of type {}", v.len(), ty)
+            }
+            Tag::TagString(s) if s.chars().count() > 64 => {
+                format!("{}...", s.chars().take(64).collect::<String>())
+            }
+            Tag::TagString(s) => s.clone(),
+            Tag::TagByteArray(v) => format!("Length of {}{}", v.len(), preview(v, 8)),
+            Tag::TagIntArray(v) => format!("Length of {}{}", v.len(), preview(v, 8)),
+            Tag::TagLongArray(v) => format!("Length of {}{}", v.len(), preview(v, 8)),
+            Tag::TagEnd => String::new(),
+            Tag::TagByte(v) => v.to_string(),
+            Tag::TagShort(v) => v.to_string(),
+            Tag::TagInt(v) => v.to_string(),
+            Tag::TagLong(v) => v.to_string(),
+            Tag::TagFloat(v) => v.to_string(),
+            Tag::TagDouble(v) => v.to_string(),
+        };
+        DebugNode {
+            type_name: self.get_name(),
+            name: name.map(str::to_string),
+            value_summary,
+            children,
+        }
+    }
+
+    /// Prints this tag (and everything in it) to stdout, in a human readable form.
+    ///
+    /// Arrays show a preview of their first 8 values.
+    pub fn pretty_print(&self, indent: usize, name: Option<&str>) {
+        let mut buf = Vec::new();
+        self.pretty_print_to(&mut buf, indent, name, 8)
+            .expect("writing to a Vec can't fail");
+        print!("{}", String::from_utf8_lossy(&buf));
+    }
+
+    /// Like `pretty_print`, but writes to `w`, and arrays show a preview of their first
+    /// `preview_len` values.
+    pub fn pretty_print_to<W>(
+        &self,
+        w: &mut W,
+        indent: usize,
+        name: Option<&str>,
+        preview_len: usize,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let name_s = name.map_or("".to_string(), |s| format!("(\"{}\")", s));
+
+        match self {
+            Tag::TagCompound(ref v) => {
+                writeln!(
+                    w,
                     "{1:0$}{2}{3} : {4} entries\n{1:0$}{{",
                     indent,
                     "",
                     self.get_name(),
                     name_s,
                     v.len()
-                );
+                )?;
                 for (name, val) in v.iter() {
-                    val.pretty_print(indent + 4, Some(name));
+                    val.pretty_print_to(w, indent + 4, Some(name), preview_len)?;
                 }
-                println!("{1:0$}}}", indent, "");
+                writeln!(w, "{1:0$}}}", indent, "")?;
             }
             Tag::TagList(ref data) => {
                 let end = Tag::TagEnd;
-                let ex = data.get(0).unwrap_or(&end);
-                println!(
+                let ex = data.first().unwrap_or(&end);
+                writeln!(
+                    w,
                     "{1:0$}{2}{3} : {4} entries of type {5}\n{1:0$}{{",
                     indent,
                     "",
@@ -314,68 +1524,345 @@ impl Tag {
                     name_s,
                     data.len(),
                     ex.get_name()
-                );
+                )?;
                 for item in data.iter() {
-                    item.pretty_print(indent + 4, None);
+                    item.pretty_print_to(w, indent + 4, None, preview_len)?;
                 }
-                println!("{1:0$}}}", indent, "");
+                writeln!(w, "{1:0$}}}", indent, "")?;
             }
             Tag::TagString(ref s) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, s)
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    s
+                )?;
             }
             Tag::TagByteArray(ref data) => {
-                println!(
-                    "{1:0$}{2}{3} : Length of {4}",
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : Length of {4}{5}",
                     indent,
                     "",
                     self.get_name(),
                     name_s,
-                    data.len()
-                );
+                    data.len(),
+                    preview(data, preview_len)
+                )?;
             }
             Tag::TagDouble(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagFloat(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagLong(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagInt(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagShort(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagByte(d) => {
-                println!("{1:0$}{2}{3} : {4}", indent, "", self.get_name(), name_s, d);
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : {4}",
+                    indent,
+                    "",
+                    self.get_name(),
+                    name_s,
+                    d
+                )?;
             }
             Tag::TagEnd => {
-                println!("{1:0$}{2}{3}", indent, "", self.get_name(), name_s);
+                writeln!(w, "{1:0$}{2}{3}", indent, "", self.get_name(), name_s)?;
             }
             Tag::TagIntArray(ref data) => {
-                println!(
-                    "{1:0$}{2}{3} : Length of {4}",
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : Length of {4}{5}",
                     indent,
                     "",
                     self.get_name(),
                     name_s,
-                    data.len()
-                );
+                    data.len(),
+                    preview(data, preview_len)
+                )?;
             }
             Tag::TagLongArray(ref data) => {
-                println!(
-                    "{1:0$}{2}{3} : Length of {4}",
+                writeln!(
+                    w,
+                    "{1:0$}{2}{3} : Length of {4}{5}",
                     indent,
                     "",
                     self.get_name(),
                     name_s,
-                    data.len()
-                );
+                    data.len(),
+                    preview(data, preview_len)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A description of the shape of a tag tree, as built by `Tag::to_debug_tree`
+///
+/// This holds the same information that `pretty_print` shows, but as data, so that tools can
+/// render it however they like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugNode {
+    /// Name of the tag's type, like "TAG_Compound"
+    pub type_name: &'static str,
+    /// The tag's key in its parent compound, if it has one
+    pub name: Option<String>,
+    /// A short description of the tag's value, with long strings and arrays cut off
+    pub value_summary: String,
+    /// The tag's children, for compounds (sorted by name) and lists
+    pub children: Vec<DebugNode>,
+}
+
+/// A single non-nested value, as passed to `TagVisitor::value`
+///
+/// Strings and arrays borrow from buffers that are reused for the next value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarTag<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    String(&'a str),
+    IntArray(&'a [i32]),
+    LongArray(&'a [i64]),
+}
+
+/// Callbacks for `Tag::parse_streaming`
+///
+/// `name` is the tag's key in its parent compound (or the root tag's name), and is `None` for
+/// list elements.  Every method does nothing by default, so only the interesting ones need to
+/// be implemented.  Returning `ControlFlow::Break` from any of them stops the parse.
+pub trait TagVisitor {
+    /// Called at the start of a compound, before any of its entries
+    fn begin_compound(&mut self, _name: Option<&str>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after the last entry of a compound
+    fn end_compound(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called at the start of a list of `len` elements of type `element_type`
+    fn begin_list(
+        &mut self,
+        _name: Option<&str>,
+        _element_type: u8,
+        _len: usize,
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after the last element of a list
+    fn end_list(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called for every tag that isn't a compound or a list
+    fn value(&mut self, _name: Option<&str>, _value: ScalarTag) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// The state of a `Tag::parse_streaming` call
+struct Streamer<'a, R, V> {
+    r: &'a mut R,
+    visitor: &'a mut V,
+    st: ParseState<'a>,
+    /// Spare buffers for compound keys, one per level of nesting in use
+    keys: Vec<Vec<u8>>,
+    /// Buffer for string and byte array values
+    text: Vec<u8>,
+    ints: Vec<i32>,
+    longs: Vec<i64>,
+}
+
+impl<'a, R, V> Streamer<'a, R, V>
+where
+    R: Read,
+    V: TagVisitor,
+{
+    fn walk(&mut self, ty: u8, name: Option<&str>) -> Result<ControlFlow<()>, Error> {
+        let value = match ty {
+            1 => ScalarTag::Byte(self.r.read_i8()?),
+            2 => ScalarTag::Short(self.r.read_i16::<BigEndian>()?),
+            3 => ScalarTag::Int(self.r.read_i32::<BigEndian>()?),
+            4 => ScalarTag::Long(self.r.read_i64::<BigEndian>()?),
+            5 => ScalarTag::Float(self.r.read_f32::<BigEndian>()?),
+            6 => ScalarTag::Double(self.r.read_f64::<BigEndian>()?),
+            7 => {
+                let len = self.r.read_u32::<BigEndian>()? as usize;
+                fill(self.r, &mut self.text, len)?;
+                ScalarTag::ByteArray(&self.text)
+            }
+            8 => {
+                let len = self.r.read_u16::<BigEndian>()? as usize;
+                self.st.charge_string(len)?;
+                fill(self.r, &mut self.text, len)?;
+                let s = Tag::decode_str(&self.text, &self.st)?;
+                return Ok(self.visitor.value(name, ScalarTag::String(&s)));
+            }
+            9 => return self.walk_list(name),
+            10 => return self.walk_compound(name),
+            11 => {
+                let len = self.r.read_u32::<BigEndian>()?;
+                self.ints.clear();
+                for _ in 0..len {
+                    self.ints.push(self.r.read_i32::<BigEndian>()?);
+                }
+                ScalarTag::IntArray(&self.ints)
+            }
+            12 => {
+                let len = self.r.read_u32::<BigEndian>()?;
+                self.longs.clear();
+                for _ in 0..len {
+                    self.longs.push(self.r.read_i64::<BigEndian>()?);
+                }
+                ScalarTag::LongArray(&self.longs)
+            }
+            x => return Err(Error::UnexpectedTag(x)),
+        };
+        Ok(self.visitor.value(name, value))
+    }
+
+    fn walk_list(&mut self, name: Option<&str>) -> Result<ControlFlow<()>, Error> {
+        let ty = self.r.read_u8()?;
+        let len = self.r.read_u32::<BigEndian>()? as usize;
+        if ty == 0 && len > 0 {
+            // TAG_End as the element type is only valid for empty lists
+            return Err(Error::InvalidList);
+        }
+        self.st.enter()?;
+        if self.visitor.begin_list(name, ty, len).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+        for _ in 0..len {
+            if self.walk(ty, None)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        self.st.leave();
+        Ok(self.visitor.end_list())
+    }
+
+    fn walk_compound(&mut self, name: Option<&str>) -> Result<ControlFlow<()>, Error> {
+        self.st.enter()?;
+        if self.visitor.begin_compound(name).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+        let mut key = self.keys.pop().unwrap_or_default();
+        loop {
+            let ty = self.r.read_u8()?;
+            if ty == 0 {
+                break;
             }
+            let len = self.r.read_u16::<BigEndian>()? as usize;
+            self.st.charge_string(len)?;
+            fill(self.r, &mut key, len)?;
+            let name = Tag::decode_str(&key, &self.st)?;
+            if self.walk(ty, Some(&name))?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        self.keys.push(key);
+        self.st.leave();
+        Ok(self.visitor.end_compound())
+    }
+}
+
+/// The most list or array elements to allocate room for up front
+///
+/// Lengths come from the data being parsed, so anything longer grows as it's read instead,
+/// rather than letting a bogus length allocate gigabytes before the data runs out.
+const MAX_PREALLOC: usize = 4096;
+
+/// Reads exactly `len` bytes into `buf`, replacing what was there
+///
+/// `buf` only grows as data actually arrives, so a bogus `len` can't allocate more than there
+/// is to read.
+fn fill<R: Read>(r: &mut R, buf: &mut Vec<u8>, len: usize) -> io::Result<()> {
+    buf.clear();
+    r.take(len as u64).read_to_end(buf)?;
+    if buf.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Formats up to `len` values of an array for `pretty_print`, like " [1, 2, 3, ...]"
+fn preview<T: std::fmt::Display>(data: &[T], len: usize) -> String {
+    if len == 0 || data.is_empty() {
+        return String::new();
+    }
+    let mut s = String::from(" [");
+    for (i, v) in data.iter().take(len).enumerate() {
+        if i > 0 {
+            s.push_str(", ");
         }
+        s.push_str(&v.to_string());
     }
+    if data.len() > len {
+        s.push_str(", ...");
+    }
+    s.push(']');
+    s
 }
 
 #[cfg(test)]
@@ -413,6 +1900,62 @@ mod test {
         assert!((motion.index(2).as_f64().unwrap() - 0.0000000).abs() < 0.00001f64);
     }
 
+    #[test]
+    fn test_len() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let data = tag.key("Data");
+        assert_eq!(data.key("GameRules").len(), Some(15));
+        assert_eq!(data.key("GameRules").is_empty(), Some(false));
+        assert_eq!(data.key("Player").key("Motion").len(), Some(3));
+        assert_eq!(data.key("thundering").len(), None);
+        assert_eq!(data.key("nope").len(), None);
+        assert_eq!((&Tag::TagList(vec![])).is_empty(), Some(true));
+    }
+
+    #[test]
+    fn test_key_ci() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let data = tag.key("Data");
+        assert_eq!(data.key_ci("gamerules").len(), Some(15));
+        assert_eq!(data.key_ci("LEVELNAME").as_string().unwrap(), "tungsten");
+        assert!(data.key_ci("nope").is_none());
+    }
+
+    #[test]
+    fn test_with_map() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let (name, last_played, thundering) = tag
+            .key("Data")
+            .with_map(|data| {
+                (
+                    data.get("LevelName").as_string().cloned(),
+                    data.get("LastPlayed").as_i64(),
+                    data.get("thundering").as_i8(),
+                )
+            })
+            .unwrap();
+        assert_eq!(name.unwrap(), "tungsten");
+        assert_eq!(last_played, Some(1424141505856));
+        assert_eq!(thundering, Some(0));
+
+        assert!(tag.key("nope").with_map(|m| m.len()).is_none());
+    }
+
     #[test]
     fn test_lifetimes() {
         use flate2::read::GzDecoder;
@@ -428,24 +1971,981 @@ mod test {
     }
 
     #[test]
-    fn test_tag_byte() {
-        let data = vec![1, 0, 5, b'h', b'e', b'l', b'l', b'o', 69];
-        test_tag(data, "hello", Tag::TagByte(69));
+    fn test_map_and_get() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+        let data = tag.key("Data").unwrap();
+
+        let (map, game_rules) = data.map_and_get("GameRules").unwrap();
+        assert_eq!(game_rules.as_map().unwrap().len(), 15);
+        assert_eq!(map.get("LastPlayed").as_i64().unwrap(), 1424141505856);
+        assert!(data.map_and_get("nope").is_none());
+        assert!(Tag::TagInt(1).map_and_get("GameRules").is_none());
     }
 
     #[test]
-    fn test_tag_byte_array() {
-        let data = vec![
-            7, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, 3, 69, 250, 123,
-        ];
-        test_tag(data, "hello", Tag::TagByteArray(vec![69, 250, 123]));
+    fn test_collect_strings() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let strings = tag.collect_strings();
+        assert!(strings.contains(&"tungsten"));
+        // GameRules are all stored as strings
+        assert!(strings.len() > 15);
+
+        let names = tag.collect_strings_by(|k| k == "LevelName");
+        assert_eq!(names, vec!["tungsten"]);
     }
 
     #[test]
-    fn test_tag_string() {
-        let data = vec![
-            8, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 3, b'c', b'a', b't',
-        ];
+    fn test_tag_cursor() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let player = TagCursor::new(&tag).key("Data").key("Player");
+        let motion = player.key("Motion");
+        assert!((motion.index(0).as_f64().unwrap() - -0.035653).abs() < 0.00001f64);
+        assert_eq!(player.key("DeathTime").as_i16(), Some(20));
+        assert!(motion.index(0).tag().is_some());
+        assert!(motion.index(9).tag().is_none());
+        assert!(player.key("nope").key("deeper").as_i32().is_none());
+    }
+
+    #[test]
+    fn test_set_path() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let mut decoder = GzDecoder::new(level_dat);
+        let (_, mut tag) = Tag::parse(&mut decoder).unwrap();
+
+        tag.set_path("Data/NewSection/flag", Tag::TagByte(1))
+            .unwrap();
+        assert_eq!(
+            tag.key("Data").key("NewSection").key("flag").as_i8(),
+            Some(1)
+        );
+        // existing siblings are untouched
+        assert_eq!(tag.key("Data").key("thundering").as_i8(), Some(0));
+
+        assert_eq!(
+            tag.set_path("Data/LastPlayed/flag", Tag::TagByte(1)),
+            Err(Error::UnexpectedType {
+                expected: "TAG_Compound",
+                found: "TAG_Long"
+            })
+        );
+
+        // the same paths work with get_path and set_path
+        tag.set_path("Data/Player/Motion[1]", Tag::TagDouble(2.5))
+            .unwrap();
+        assert_eq!(
+            tag.get_path("Data/Player/Motion[1]"),
+            Some(&Tag::TagDouble(2.5))
+        );
+        assert_eq!(
+            tag.set_path("Data/Player/Motion[3]", Tag::TagDouble(2.5))
+                .unwrap_err()
+                .io_kind(),
+            Some(io::ErrorKind::InvalidInput)
+        );
+        assert_eq!(
+            tag.set_path("Data/Player[0]", Tag::TagDouble(2.5)),
+            Err(Error::UnexpectedType {
+                expected: "TAG_List",
+                found: "TAG_Compound"
+            })
+        );
+    }
+
+    #[test]
+    fn test_list_push() {
+        let item = |id: &str, slot: i8| {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), Tag::TagString(id.to_string()));
+            m.insert("Slot".to_string(), Tag::TagByte(slot));
+            Tag::TagCompound(m)
+        };
+        let mut inventory = Tag::TagList(vec![item("minecraft:dirt", 0)]);
+
+        inventory.list_push(item("minecraft:stone", 1)).unwrap();
+        assert_eq!((&inventory).len(), Some(2));
+        assert_eq!(
+            inventory.list_push(Tag::TagInt(3)),
+            Err(Error::UnexpectedType {
+                expected: "TAG_Compound",
+                found: "TAG_Int"
+            })
+        );
+        assert_eq!((&inventory).len(), Some(2));
+
+        inventory
+            .list_get_mut(1)
+            .unwrap()
+            .set_path("Count", Tag::TagByte(64))
+            .unwrap();
+        assert_eq!(inventory.index(1).key("Count").as_i8(), Some(64));
+        assert!(inventory.list_get_mut(2).is_none());
+        assert!(Tag::TagInt(1).list_push(Tag::TagInt(2)).is_err());
+    }
+
+    #[test]
+    fn test_get_path() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        assert_eq!(
+            tag.get_path("Data/LevelName").as_string().unwrap(),
+            "tungsten"
+        );
+        let motion = tag.get_path("Data/Player/Motion[0]").as_f64().unwrap();
+        assert!((motion - -0.035653).abs() < 0.00001);
+        assert_eq!(
+            tag.get_path("Data/Player/Motion"),
+            tag.key("Data").key("Player").key("Motion")
+        );
+        assert_eq!(tag.get_path(""), Some(&tag));
+
+        assert_eq!(tag.get_path("Data/Player/Motion[3]"), None);
+        assert_eq!(tag.get_path("Data/Nope"), None);
+        assert_eq!(tag.get_path("Data[0]"), None);
+        assert_eq!(tag.get_path("Data/Player/Motion[x]"), None);
+        assert_eq!(tag.get_path("Data/Player/Motion[0"), None);
+        assert_eq!(tag.get_path("Data/Player/Motion[0]x"), None);
+
+        let nested = Tag::TagList(vec![Tag::TagList(vec![Tag::TagInt(1), Tag::TagInt(2)])]);
+        assert_eq!(nested.get_path("[0][1]"), Some(&Tag::TagInt(2)));
+    }
+
+    #[test]
+    fn test_get_path_mut() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (_, mut tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let path = [
+            PathSeg::Key("Data".to_string()),
+            PathSeg::Key("Player".to_string()),
+            PathSeg::Key("Motion".to_string()),
+        ];
+        let motion = tag.get_path_mut(&path).unwrap();
+        *motion.list_get_mut(1).unwrap() = Tag::TagDouble(2.5);
+        assert_eq!(
+            tag.key("Data")
+                .key("Player")
+                .key("Motion")
+                .index(1)
+                .as_f64(),
+            Some(2.5)
+        );
+
+        let mut path = path.to_vec();
+        path.push(PathSeg::Index(0));
+        assert!(tag.get_path_mut(&path).is_some());
+        path[3] = PathSeg::Index(3);
+        assert!(tag.get_path_mut(&path).is_none());
+        path[3] = PathSeg::Key("0".to_string());
+        assert!(tag.get_path_mut(&path).is_none());
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let item = |count: i32| {
+            let mut map = HashMap::new();
+            map.insert(
+                "id".to_string(),
+                Tag::TagString("minecraft:stone".to_string()),
+            );
+            map.insert("Count".to_string(), Tag::TagInt(count));
+            Tag::TagCompound(map)
+        };
+        let mut root = HashMap::new();
+        root.insert("Items".to_string(), Tag::TagList(vec![item(1), item(5)]));
+        root.insert("Count".to_string(), Tag::TagShort(2));
+        root.insert("Total".to_string(), Tag::TagInt(6));
+        let mut tag = Tag::TagCompound(root);
+
+        let mut visited = Vec::new();
+        tag.visit_mut(&mut |path, t| {
+            visited.push(path.to_vec());
+            if let (Some(PathSeg::Key(key)), Tag::TagInt(i)) = (path.last(), t) {
+                if key == "Count" {
+                    *i *= 10;
+                }
+            }
+        });
+
+        let items = tag.key("Items");
+        assert_eq!(items.index(0).key("Count").as_i32(), Some(10));
+        assert_eq!(items.index(1).key("Count").as_i32(), Some(50));
+        // only ints were changed, and only under "Count"
+        assert_eq!(tag.key("Count"), Some(&Tag::TagShort(2)));
+        assert_eq!(tag.key("Total").as_i32(), Some(6));
+
+        // every tag is visited once, and each list is visited before its elements
+        assert_eq!(visited.len(), 10);
+        assert_eq!(visited[0], vec![]);
+        let list_pos = visited
+            .iter()
+            .position(|p| p.len() == 1 && p[0] == PathSeg::Key("Items".to_string()));
+        let elem_pos = visited
+            .iter()
+            .position(|p| p.len() == 2 && p[1] == PathSeg::Index(1));
+        assert!(list_pos < elem_pos);
+    }
+
+    #[test]
+    fn test_remove_path() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let mut decoder = GzDecoder::new(level_dat);
+        let (_, mut tag) = Tag::parse(&mut decoder).unwrap();
+
+        let abilities = tag.remove_path("Data/Player/abilities").unwrap();
+        assert!(abilities.key("flying").is_some());
+        assert!(tag.key("Data").key("Player").key("abilities").is_none());
+        assert!(tag.remove_path("Data/Player/abilities").is_none());
+        assert!(tag.remove_path("Data/Nope/abilities").is_none());
+
+        // lists are indexed in brackets, just like with get_path
+        assert!(tag.remove_path("Data/Player/Motion/1").is_none());
+        let motion = tag.remove_path("Data/Player/Motion[1]").unwrap();
+        assert!(((&motion).as_f64().unwrap() - -1.062104).abs() < 0.00001f64);
+        assert_eq!(tag.key("Data").key("Player").key("Motion").len(), Some(2));
+    }
+
+    #[test]
+    fn test_as_scalar() {
+        let tag = Tag::TagShort(7);
+        let scalar = (&tag).as_scalar().unwrap();
+        assert_eq!(
+            scalar,
+            Scalar::Int {
+                value: 7,
+                type_id: 2
+            }
+        );
+        assert_eq!(scalar.type_id(), tag.type_id());
+        assert_eq!(scalar.to_tag(), tag);
+
+        let tag = Tag::TagFloat(1.5);
+        assert_eq!((&tag).as_scalar().unwrap().to_tag(), tag);
+        assert!((&Tag::TagString("7".to_string())).as_scalar().is_none());
+    }
+
+    #[test]
+    fn test_as_enum() {
+        #[derive(Debug, PartialEq)]
+        enum Status {
+            Features,
+            Full,
+        }
+        impl FromStr for Status {
+            type Err = ();
+            fn from_str(s: &str) -> Result<Status, ()> {
+                match s {
+                    "features" => Ok(Status::Features),
+                    "full" => Ok(Status::Full),
+                    _ => Err(()),
+                }
+            }
+        }
+
+        let mut m = HashMap::new();
+        m.insert("Status".to_string(), Tag::TagString("full".to_string()));
+        m.insert("Other".to_string(), Tag::TagString("carvers".to_string()));
+        m.insert("Number".to_string(), Tag::TagInt(1));
+        let chunk = Tag::TagCompound(m);
+
+        assert_eq!(chunk.key("Status").as_enum(), Some(Status::Full));
+        assert_eq!(chunk.key("Other").as_enum::<Status>(), None);
+        assert_eq!(chunk.key("Number").as_enum::<Status>(), None);
+        assert_eq!(chunk.key("Number").as_enum::<i32>(), None);
+    }
+
+    #[test]
+    fn test_as_pos() {
+        let pos = Tag::TagList(vec![
+            Tag::TagDouble(10.5),
+            Tag::TagDouble(64.0),
+            Tag::TagDouble(-3.25),
+        ]);
+        assert_eq!((&pos).as_fpos(), Some([10.5, 64.0, -3.25]));
+        assert_eq!((&pos).as_ipos(), Some([10, 64, -4]));
+
+        let block_pos = Tag::TagIntArray(vec![1, 70, -5]);
+        assert_eq!((&block_pos).as_ipos(), Some([1, 70, -5]));
+        assert_eq!((&block_pos).as_fpos(), Some([1.0, 70.0, -5.0]));
+
+        let short = Tag::TagList(vec![Tag::TagInt(1), Tag::TagInt(2)]);
+        assert!((&short).as_ipos().is_none());
+        let strings = Tag::TagList(vec![Tag::TagString("1".to_string()); 3]);
+        assert!((&strings).as_fpos().is_none());
+    }
+
+    #[test]
+    fn test_write_roundtrip() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+        use std::io::Cursor;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (name, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let mut buf = Vec::new();
+        tag.write(&mut buf, &name).unwrap();
+        let (new_name, new_tag) = Tag::parse(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(new_name, name);
+        assert_eq!(new_tag, tag);
+    }
+
+    #[test]
+    fn test_write_tag_end_child() {
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), Tag::TagInt(1));
+        map.insert("end".to_string(), Tag::TagEnd);
+        let mut buf = Vec::new();
+        assert_eq!(
+            Tag::TagCompound(map).write(&mut buf, ""),
+            Err(Error::UnexpectedType {
+                expected: "a tag other than TAG_End",
+                found: "TAG_End"
+            })
+        );
+
+        let list = Tag::TagList(vec![Tag::TagEnd]);
+        assert_eq!(list.write(&mut Vec::new(), ""), Err(Error::InvalidList));
+    }
+
+    #[test]
+    fn test_write_le_roundtrip() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+        use std::io::Cursor;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let mut decoder = GzDecoder::new(level_dat);
+        let (name, tag) = Tag::parse(&mut decoder).unwrap();
+
+        let mut buf = Vec::new();
+        tag.write_le(&mut buf, &name).unwrap();
+        let (le_name, le_tag) = Tag::parse_le(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(le_name, name);
+        assert_eq!(le_tag, tag);
+
+        // the two encodings should actually differ
+        let mut be = Vec::new();
+        tag.write(&mut be, &name).unwrap();
+        assert_ne!(be, buf);
+    }
+
+    #[test]
+    fn test_write_compressed() {
+        use flate2::read::{GzDecoder, ZlibDecoder};
+        use std::fs;
+
+        let level_dat = fs::File::open("tests/data/level.dat").unwrap();
+        let (name, tag) = Tag::parse(&mut GzDecoder::new(level_dat)).unwrap();
+
+        let mut buf = Vec::new();
+        tag.write_compressed(&mut buf, &name, Compression::Gzip)
+            .unwrap();
+        let (n, t) = Tag::parse(&mut GzDecoder::new(&buf[..])).unwrap();
+        assert_eq!((n, t), (name.clone(), tag.clone()));
+
+        let mut buf = Vec::new();
+        tag.write_compressed(&mut buf, &name, Compression::Zlib)
+            .unwrap();
+        let (_, t) = Tag::parse(&mut ZlibDecoder::new(&buf[..])).unwrap();
+        assert_eq!(t, tag);
+
+        let mut buf = Vec::new();
+        tag.write_compressed(&mut buf, &name, Compression::None)
+            .unwrap();
+        let (_, t) = Tag::parse(&mut &buf[..]).unwrap();
+        assert_eq!(t, tag);
+    }
+
+    #[test]
+    fn test_peek_data_version() {
+        use crate::RegionFile;
+        use std::fs::File;
+        use std::io::Cursor;
+
+        // a chunk from before 1.9, which has no DataVersion
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let chunk = RegionFile::new(f).unwrap().load_chunk(0, 0).unwrap();
+        let mut buf = Vec::new();
+        chunk.write(&mut buf, "").unwrap();
+        assert_eq!(
+            Tag::peek_data_version(&mut Cursor::new(&buf)).unwrap(),
+            None
+        );
+
+        // everything before DataVersion is skipped, and nothing after it is read
+        let mut m = HashMap::new();
+        m.insert("Level".to_string(), chunk);
+        let mut buf = Vec::new();
+        Tag::TagCompound(m).write(&mut buf, "").unwrap();
+        buf.pop();
+        buf.extend_from_slice(&[3, 0, 11]);
+        buf.extend_from_slice(b"DataVersion");
+        buf.extend_from_slice(&2975i32.to_be_bytes());
+        buf.extend_from_slice(&[42, 42, 42]);
+        assert!(Tag::parse(&mut Cursor::new(&buf)).is_err());
+        assert_eq!(
+            Tag::peek_data_version(&mut Cursor::new(&buf)).unwrap(),
+            Some(2975)
+        );
+
+        // deeply nested lists before DataVersion hit the depth limit instead of the stack's
+        let mut buf = vec![10, 0, 0, 9, 0, 1, b'l', 9, 0, 0, 0, 1];
+        for _ in 0..200_000 {
+            buf.extend_from_slice(&[9, 0, 0, 0, 1]);
+        }
+        assert_eq!(
+            Tag::peek_data_version(&mut Cursor::new(&buf)),
+            Err(Error::DepthLimitExceeded)
+        );
+
+        // a list of TAG_End claiming to have elements is rejected without looping over them
+        let buf = vec![10, 0, 0, 9, 0, 1, b'l', 0, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(
+            Tag::peek_data_version(&mut Cursor::new(&buf)),
+            Err(Error::InvalidList)
+        );
+    }
+
+    #[test]
+    fn test_parse_compressed() {
+        use std::fs;
+
+        let gz = fs::read("tests/data/level.dat").unwrap();
+        let (name, tag) = Tag::parse_compressed(&mut &gz[..]).unwrap();
+        assert_eq!(
+            tag.key("Data").key("LastPlayed").as_i64(),
+            Some(1424141505856)
+        );
+
+        let mut zlib = Vec::new();
+        tag.write_compressed(&mut zlib, &name, Compression::Zlib)
+            .unwrap();
+        assert_eq!(Tag::parse_compressed(&mut &zlib[..]).unwrap().1, tag);
+
+        let mut raw = Vec::new();
+        tag.write(&mut raw, &name).unwrap();
+        assert_eq!(Tag::parse_compressed(&mut &raw[..]).unwrap().1, tag);
+
+        let data = [3, 0, 0, 0, 0, 0, 1];
+        assert_eq!(
+            Tag::parse_compressed(&mut &data[..]),
+            Err(Error::UnsupportedCompressionFormat {
+                compression_type: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_modified_utf8_round_trip() {
+        use std::io::Cursor;
+
+        let tag = Tag::TagString("sign\0text \u{1f600}".to_string());
+        let mut data = Vec::new();
+        tag.write(&mut data, "").unwrap();
+        // the null and the emoji are both written in their modified UTF-8 forms
+        assert_eq!(&data[3..5], &[0, 17]);
+        assert!(!data[5..].contains(&0));
+
+        let (_, parsed) = Tag::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(parsed, tag);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        use std::io::Cursor;
+
+        // a root compound holding a list of lists of lists...
+        let nested = |depth: usize| {
+            let mut data = vec![10, 0, 0, 9, 0, 0];
+            for _ in 2..depth {
+                data.extend_from_slice(&[9, 0, 0, 0, 1]);
+            }
+            data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+            data
+        };
+
+        let data = nested(100);
+        assert!(Tag::parse_with_limits(&mut Cursor::new(&data), 100).is_ok());
+        assert_eq!(
+            Tag::parse_with_limits(&mut Cursor::new(&data), 99),
+            Err(Error::DepthLimitExceeded)
+        );
+
+        assert!(Tag::parse(&mut Cursor::new(&nested(512))).is_ok());
+        // far too deep to recurse through, but still a clean error
+        let data = nested(100_000);
+        assert_eq!(
+            Tag::parse(&mut Cursor::new(&data)),
+            Err(Error::DepthLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_parse_streaming() {
+        use flate2::read::GzDecoder;
+        use std::fs;
+
+        /// Counts everything, and remembers the path to and value of `LastPlayed`
+        #[derive(Default)]
+        struct Counter {
+            path: Vec<String>,
+            compounds: usize,
+            lists: usize,
+            values: usize,
+            last_played: Option<(String, i64)>,
+            stop_at_last_played: bool,
+        }
+
+        impl TagVisitor for Counter {
+            fn begin_compound(&mut self, name: Option<&str>) -> ControlFlow<()> {
+                self.compounds += 1;
+                self.path.push(name.unwrap_or("*").to_string());
+                ControlFlow::Continue(())
+            }
+            fn end_compound(&mut self) -> ControlFlow<()> {
+                self.path.pop();
+                ControlFlow::Continue(())
+            }
+            fn begin_list(&mut self, _: Option<&str>, _: u8, _: usize) -> ControlFlow<()> {
+                self.lists += 1;
+                self.path.push("*".to_string());
+                ControlFlow::Continue(())
+            }
+            fn end_list(&mut self) -> ControlFlow<()> {
+                self.path.pop();
+                ControlFlow::Continue(())
+            }
+            fn value(&mut self, name: Option<&str>, value: ScalarTag) -> ControlFlow<()> {
+                self.values += 1;
+                if let (Some("LastPlayed"), ScalarTag::Long(v)) = (name, value) {
+                    self.last_played = Some((self.path.join("/"), v));
+                    if self.stop_at_last_played {
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+        }
+
+        let data = fs::read("tests/data/level.dat").unwrap();
+        let (_, tag) = Tag::parse(&mut GzDecoder::new(&data[..])).unwrap();
+
+        let mut counter = Counter::default();
+        Tag::parse_streaming(&mut GzDecoder::new(&data[..]), &mut counter).unwrap();
+        assert_eq!(counter.values, tag.flatten().len());
+        assert!(counter.path.is_empty());
+        assert_eq!(
+            counter.last_played,
+            Some(("/Data".to_string(), 1424141505856))
+        );
+
+        let mut early = Counter {
+            stop_at_last_played: true,
+            ..Default::default()
+        };
+        Tag::parse_streaming(&mut GzDecoder::new(&data[..]), &mut early).unwrap();
+        assert_eq!(early.last_played, counter.last_played);
+        assert!(early.values < counter.values);
+        assert_eq!(early.path, vec!["", "Data"]);
+    }
+
+    #[test]
+    fn test_signed_arrays() {
+        use std::io::Cursor;
+
+        let data = vec![11, 0, 0, 0, 2, 0xff, 0xff, 0xff, 0xfe, 0, 0, 0, 3];
+        let tag = Tag::parse_tag(&mut Cursor::new(&data), None).unwrap();
+        assert_eq!((&tag).as_ints(), Some(&vec![-2, 3]));
+
+        let tag = Tag::TagLongArray(vec![-1, i64::MIN]);
+        let mut data = Vec::new();
+        tag.write(&mut data, "").unwrap();
+        assert_eq!(Tag::parse(&mut Cursor::new(&data)).unwrap().1, tag);
+    }
+
+    #[test]
+    fn test_string_budget() {
+        use std::io::Cursor;
+
+        let long = "x".repeat(65535);
+        let list = Tag::TagList(vec![Tag::TagString(long); 100]);
+        let mut map = HashMap::new();
+        map.insert("strings".to_string(), list);
+        let mut data = Vec::new();
+        Tag::TagCompound(map).write(&mut data, "").unwrap();
+
+        let opts = ParseOptions {
+            max_total_string_bytes: Some(65535 * 10),
+            ..Default::default()
+        };
+        assert_eq!(
+            Tag::parse_with_options(&mut Cursor::new(&data), &opts),
+            Err(Error::StringBudgetExceeded)
+        );
+
+        // the budget covers every string, including keys, so this is just enough
+        let opts = ParseOptions {
+            max_total_string_bytes: Some(65535 * 100 + "strings".len()),
+            ..Default::default()
+        };
+        assert!(Tag::parse_with_options(&mut Cursor::new(&data), &opts).is_ok());
+    }
+
+    #[test]
+    fn test_bogus_lengths() {
+        use std::io::Cursor;
+
+        // each claims about 4 billion elements, but the data runs out right away
+        for data in [
+            vec![7, 0, 0, 0xff, 0xff, 0xff, 0xff, 1, 2],
+            vec![9, 0, 0, 3, 0xff, 0xff, 0xff, 0xff, 1, 2],
+            vec![11, 0, 0, 0xff, 0xff, 0xff, 0xff, 1, 2],
+            vec![12, 0, 0, 0xff, 0xff, 0xff, 0xff, 1, 2],
+        ] {
+            let err = Tag::parse(&mut Cursor::new(&data)).unwrap_err();
+            assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+        }
+    }
+
+    #[test]
+    fn test_lossy_strings() {
+        use std::io::Cursor;
+
+        let data = vec![8, 0, 1, b'a', 0, 3, b'c', 0xff, b't'];
+        assert!(matches!(
+            Tag::parse(&mut Cursor::new(&data)),
+            Err(Error::BadEncoding(_))
+        ));
+
+        let opts = ParseOptions {
+            lossy_strings: true,
+            ..Default::default()
+        };
+        let (_, tag) = Tag::parse_with_options(&mut Cursor::new(&data), &opts).unwrap();
+        assert_eq!(tag, Tag::TagString("c\u{fffd}t".to_string()));
+    }
+
+    #[test]
+    fn test_modified_utf8_strings() {
+        use std::io::Cursor;
+
+        // U+1F600 as a surrogate pair, the way Java writes it
+        let data = vec![8, 0, 0, 0, 6, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+        let (_, tag) = Tag::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(tag, Tag::TagString("\u{1f600}".to_string()));
+
+        // a dangling high surrogate
+        let data = vec![8, 0, 0, 0, 5, b'a', b'b', 0xed, 0xa0, 0xbd];
+        assert_eq!(
+            Tag::parse(&mut Cursor::new(&data)),
+            Err(Error::BadModifiedUtf8 { offset: 2 })
+        );
+    }
+
+    #[test]
+    fn test_bad_root_name() {
+        use std::io::Cursor;
+
+        let data = vec![3, 0, 3, b'a', 0xff, b'b', 0, 0, 0, 7];
+        let (name, tag) = Tag::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(name, "a\u{fffd}b");
+        assert_eq!(tag, Tag::TagInt(7));
+    }
+
+    #[test]
+    fn test_untrusted_list_type() {
+        use std::io::Cursor;
+
+        #[rustfmt::skip]
+        let data = vec![
+            9, 0, 0,
+            // declared as a list of two bytes, but each element is tagged with its real type
+            1, 0, 0, 0, 2,
+            2, 1, 0,
+            1, 7,
+        ];
+        // strict parsing misreads the type bytes as values
+        let (_, tag) = Tag::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(tag, Tag::TagList(vec![Tag::TagByte(2), Tag::TagByte(1)]));
+
+        let opts = ParseOptions {
+            trust_list_type: false,
+            ..Default::default()
+        };
+        let (_, tag) = Tag::parse_with_options(&mut Cursor::new(&data), &opts).unwrap();
+        assert_eq!(tag, Tag::TagList(vec![Tag::TagShort(256), Tag::TagByte(7)]));
+    }
+
+    #[test]
+    fn test_pluck() {
+        let item = |id: &str, count: i8| {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), Tag::TagString(id.to_string()));
+            m.insert("Count".to_string(), Tag::TagByte(count));
+            Tag::TagCompound(m)
+        };
+        let list = Tag::TagList(vec![
+            item("minecraft:dirt", 3),
+            Tag::TagCompound(HashMap::new()),
+            item("minecraft:stone", 64),
+        ]);
+
+        let ids = (&list).pluck("id").unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0].as_string().unwrap(), "minecraft:dirt");
+        assert_eq!(ids[1].as_string().unwrap(), "minecraft:stone");
+        assert!((&Tag::TagInt(1)).pluck("id").is_none());
+    }
+
+    #[test]
+    fn test_skip_unknown_tag() {
+        use std::io::Cursor;
+
+        #[rustfmt::skip]
+        let data = vec![
+            10, 0, 0,
+            1, 0, 1, b'a', 5,
+            // a made up tag type 42, with a 4 byte payload
+            42, 0, 1, b'x', 1, 2, 3, 4,
+            3, 0, 1, b'b', 0, 0, 0, 7,
+            0,
+        ];
+        assert_eq!(
+            Tag::parse(&mut Cursor::new(&data)),
+            Err(Error::UnexpectedTag(42))
+        );
+
+        let opts = ParseOptions {
+            on_unknown_tag: UnknownTagPolicy::Skip(4),
+            ..Default::default()
+        };
+        let (_, tag) = Tag::parse_with_options(&mut Cursor::new(&data), &opts).unwrap();
+        assert_eq!((&tag).len(), Some(2));
+        assert_eq!(tag.key("a").as_i8(), Some(5));
+        assert_eq!(tag.key("b").as_i32(), Some(7));
+    }
+
+    #[test]
+    fn test_sort_list_by_key() {
+        let item = |slot: Option<i8>, id: &str| {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), Tag::TagString(id.to_string()));
+            if let Some(slot) = slot {
+                m.insert("Slot".to_string(), Tag::TagByte(slot));
+            }
+            Tag::TagCompound(m)
+        };
+        let mut list = Tag::TagList(vec![
+            item(Some(5), "a"),
+            item(None, "b"),
+            item(Some(1), "c"),
+            item(None, "d"),
+            item(Some(3), "e"),
+        ]);
+
+        list.sort_list_by_key("Slot");
+        let ids: Vec<&str> = (&list)
+            .pluck("id")
+            .unwrap()
+            .iter()
+            .map(|t| t.as_string().unwrap().as_str())
+            .collect();
+        assert_eq!(ids, vec!["c", "e", "a", "b", "d"]);
+
+        list.sort_list_by_key("id");
+        let ids: Vec<&str> = (&list)
+            .pluck("id")
+            .unwrap()
+            .iter()
+            .map(|t| t.as_string().unwrap().as_str())
+            .collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_normalize_ids() {
+        let item = |key: &str, id: &str| {
+            let mut m = HashMap::new();
+            m.insert(key.to_string(), Tag::TagString(id.to_string()));
+            Tag::TagCompound(m)
+        };
+        let mut root = HashMap::new();
+        root.insert(
+            "Items".to_string(),
+            Tag::TagList(vec![item("id", "stone"), item("id", "mymod:thing")]),
+        );
+        root.insert("block".to_string(), item("Name", "dirt"));
+        root.insert("other".to_string(), item("CustomName", "stone"));
+        let mut tag = Tag::TagCompound(root);
+
+        tag.normalize_ids("minecraft");
+        let items = tag.key("Items");
+        assert_eq!(
+            items.index(0).key("id").as_string().unwrap(),
+            "minecraft:stone"
+        );
+        assert_eq!(items.index(1).key("id").as_string().unwrap(), "mymod:thing");
+        assert_eq!(
+            tag.key("block").key("Name").as_string().unwrap(),
+            "minecraft:dirt"
+        );
+        assert_eq!(
+            tag.key("other").key("CustomName").as_string().unwrap(),
+            "stone"
+        );
+    }
+
+    #[test]
+    fn test_truncate_arrays() {
+        let mut m = HashMap::new();
+        m.insert("big".to_string(), Tag::TagIntArray(vec![7; 5000]));
+        m.insert(
+            "small".to_string(),
+            Tag::TagList(vec![Tag::TagByte(1), Tag::TagByte(2)]),
+        );
+        let mut tag = Tag::TagCompound(m);
+
+        tag.truncate_arrays(16);
+        assert_eq!(tag.key("big").as_ints().unwrap().len(), 16);
+        assert_eq!(tag.key("small").len(), Some(2));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let mut player = HashMap::new();
+        player.insert(
+            "Pos".to_string(),
+            Tag::TagList(vec![Tag::TagDouble(1.0), Tag::TagDouble(2.0)]),
+        );
+        player.insert("Inventory".to_string(), Tag::TagList(vec![]));
+        player.insert("Data".to_string(), Tag::TagIntArray(vec![1, 2, 3]));
+        let mut root = HashMap::new();
+        root.insert("Player".to_string(), Tag::TagCompound(player));
+        let tag = Tag::TagCompound(root);
+
+        let mut flat = tag.flatten();
+        flat.sort_by(|a, b| a.0.cmp(&b.0));
+        let paths: Vec<&str> = flat.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["Player.Data", "Player.Pos.0", "Player.Pos.1"]);
+        assert_eq!(flat[2].1, &Tag::TagDouble(2.0));
+
+        assert_eq!(
+            Tag::TagInt(5).flatten(),
+            vec![(String::new(), &Tag::TagInt(5))]
+        );
+    }
+
+    #[test]
+    fn test_debug_tree() {
+        let mut m = HashMap::new();
+        m.insert("b".to_string(), Tag::TagIntArray((0..20).collect()));
+        m.insert(
+            "a".to_string(),
+            Tag::TagList(vec![Tag::TagShort(1), Tag::TagShort(2)]),
+        );
+        m.insert("c".to_string(), Tag::TagString("x".repeat(100)));
+        let tree = Tag::TagCompound(m).to_debug_tree();
+
+        assert_eq!(tree.type_name, "TAG_Compound");
+        assert_eq!(tree.name, None);
+        assert_eq!(tree.value_summary, "3 entries");
+        assert_eq!(tree.children.len(), 3);
+
+        let names: Vec<_> = tree.children.iter().map(|c| c.type_name).collect();
+        assert_eq!(names, vec!["TAG_List", "TAG_IntArray", "TAG_String"]);
+        assert_eq!(tree.children[0].name.as_deref(), Some("a"));
+        assert_eq!(
+            tree.children[0].value_summary,
+            "2 entries of type TAG_Short"
+        );
+        assert_eq!(tree.children[0].children[1].value_summary, "2");
+        assert_eq!(
+            tree.children[1].value_summary,
+            "Length of 20 [0, 1, 2, 3, 4, 5, 6, 7, ...]"
+        );
+        assert_eq!(tree.children[2].value_summary.len(), 67);
+    }
+
+    #[test]
+    fn test_pretty_print_preview() {
+        let mut out = Vec::new();
+        Tag::TagIntArray(vec![1, 2, 3])
+            .pretty_print_to(&mut out, 0, Some("a"), 8)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TAG_IntArray(\"a\") : Length of 3 [1, 2, 3]\n"
+        );
+
+        let mut out = Vec::new();
+        Tag::TagByteArray((0..10).collect())
+            .pretty_print_to(&mut out, 2, None, 4)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "  TAG_ByteArray : Length of 10 [0, 1, 2, 3, ...]\n"
+        );
+    }
+
+    #[test]
+    fn test_tag_byte() {
+        let data = vec![1, 0, 5, b'h', b'e', b'l', b'l', b'o', 69];
+        test_tag(data, "hello", Tag::TagByte(69));
+    }
+
+    #[test]
+    fn test_tag_byte_array() {
+        let data = vec![
+            7, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, 3, 69, 250, 123,
+        ];
+        test_tag(data, "hello", Tag::TagByteArray(vec![69, 250, 123]));
+    }
+
+    #[test]
+    fn test_bytes_match() {
+        use std::io::Cursor;
+
+        let data = vec![
+            7, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, 3, 69, 250, 123,
+        ];
+        let (name, tag) = Tag::parse(&mut Cursor::new(&data)).unwrap();
+        assert!(tag.bytes_match(&name, &data));
+        assert!(!tag.bytes_match("world", &data));
+        assert!(!Tag::TagByteArray(vec![69]).bytes_match(&name, &data));
+    }
+
+    #[test]
+    fn test_tag_string() {
+        let data = vec![
+            8, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 3, b'c', b'a', b't',
+        ];
         test_tag(data, "hello", Tag::TagString("cat".to_string()));
     }
 
@@ -458,4 +2958,34 @@ mod test {
             Tag::TagList(vec![Tag::TagByte(1), Tag::TagByte(2), Tag::TagByte(3)]),
         );
     }
+
+    #[test]
+    fn test_write_list() {
+        let mut buf = Vec::new();
+        Tag::TagList(vec![]).write(&mut buf, "hi").unwrap();
+        assert_eq!(buf, vec![9, 0, 2, b'h', b'i', 0, 0, 0, 0, 0]);
+
+        let mut buf = Vec::new();
+        Tag::TagList(vec![Tag::TagByte(1), Tag::TagByte(2), Tag::TagByte(3)])
+            .write(&mut buf, "hi")
+            .unwrap();
+        assert_eq!(buf, vec![9, 0, 2, b'h', b'i', 1, 0, 0, 0, 3, 1, 2, 3]);
+
+        let mut buf = Vec::new();
+        assert_eq!(
+            Tag::TagList(vec![Tag::TagByte(1), Tag::TagShort(2)]).write(&mut buf, "hi"),
+            Err(Error::HeterogeneousList)
+        );
+    }
+
+    #[test]
+    fn test_tag_list_of_end() {
+        use std::io::Cursor;
+
+        let data = vec![9, 0, 2, b'h', b'i', 0, 0, 0, 0, 0];
+        test_tag(data, "hi", Tag::TagList(vec![]));
+
+        let data = vec![9, 0, 2, b'h', b'i', 0, 0, 0, 0, 3];
+        assert_eq!(Tag::parse(&mut Cursor::new(data)), Err(Error::InvalidList));
+    }
 }
@@ -1,62 +1,255 @@
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::bytes::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crate::error as nbt_error;
-use crate::nbt;
+use crate::nbt::{self, Taglike};
 
 /// A region file
 ///
 /// These normally have a .mca extension on disk.  They contain up to 1024 chunks, each containing
-/// a 32-by-32 column of blocks.
+/// a 32-by-32 column of blocks.  Older McRegion files (.mcr) share the same layout, and can be
+/// read as well.
 #[allow(dead_code)]
 pub struct RegionFile<T> {
     /// Offsets (in bytes, from the beginning of the file) of each chunk.
     /// An offset of zero means the chunk does not exist
-    offsets: Vec<u32>,
+    offsets: Vec<u64>,
 
     /// Timestamps, indexed by chunk.  If the chunk doesn't exist, the value will be zero
     timestamps: Vec<u32>,
 
-    /// Size of each chunk, in number of 4096-byte sectors
+    /// Size of each chunk, in number of sectors
     chunk_size: Vec<u8>,
 
+    /// Size of a sector in bytes, which is 4096 for every standard region file
+    sector_size: u32,
+
+    /// Which chunks exist, one u32 per row (z) with bit x set if chunk (x, z) exists
+    present: [u32; 32],
+
+    /// Which sectors of the file are in use, either by the header or by a chunk
+    used_sectors: Vec<bool>,
+
     cursor: Box<T>,
 }
 
+/// Readers that can be duplicated into an independent handle, with its own position
+///
+/// This isn't implemented for `File`, since `File::try_clone` shares its seek position with
+/// the original, so reads from two clones on different threads could interleave.
+pub trait TryClone: Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl<T: Clone> TryClone for Cursor<T> {
+    fn try_clone(&self) -> io::Result<Cursor<T>> {
+        Ok(self.clone())
+    }
+}
+
+impl<R> RegionFile<R>
+where
+    R: TryClone,
+{
+    /// Creates a new handle to the same region, without re-parsing the header.
+    ///
+    /// The new handle has its own copy of the header and its own reader, cloned with
+    /// `TryClone`, so the two can load chunks from different threads.  For a region on disk,
+    /// open the file once per thread instead.
+    pub fn try_clone(&self) -> io::Result<RegionFile<R>> {
+        Ok(RegionFile {
+            offsets: self.offsets.clone(),
+            timestamps: self.timestamps.clone(),
+            chunk_size: self.chunk_size.clone(),
+            sector_size: self.sector_size,
+            present: self.present,
+            used_sectors: self.used_sectors.clone(),
+            cursor: Box::new(self.cursor.try_clone()?),
+        })
+    }
+}
+
+impl<'a> RegionFile<Cursor<&'a [u8]>> {
+    /// Parses a region file that is already in memory
+    pub fn from_slice(data: &'a [u8]) -> Result<RegionFile<Cursor<&'a [u8]>>, nbt_error::Error> {
+        RegionFile::new(Cursor::new(data))
+    }
+}
+
+impl RegionFile<Cursor<Vec<u8>>> {
+    /// Parses a region file that has been gzipped as a whole, like a `.mca.gz` backup.
+    ///
+    /// Since chunks need to be read out of order, the entire region is decompressed into
+    /// memory first.
+    pub fn new_gz<R>(r: R) -> Result<RegionFile<Cursor<Vec<u8>>>, nbt_error::Error>
+    where
+        R: Read,
+    {
+        let mut data = Vec::new();
+        GzDecoder::new(r).read_to_end(&mut data)?;
+        RegionFile::new(Cursor::new(data))
+    }
+
+    /// Parses a region file from an entry in a zip archive, like a zipped world backup.
+    ///
+    /// Zip entries can only be read front to back, so the whole entry is read into memory
+    /// first.  This costs as much memory as the uncompressed region, which is up to a few
+    /// megabytes for a fully generated one.  `entry` can be anything readable, such as the
+    /// `ZipFile` from the `zip` crate.
+    pub fn from_zip_entry<R>(mut entry: R) -> Result<RegionFile<Cursor<Vec<u8>>>, nbt_error::Error>
+    where
+        R: Read,
+    {
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        RegionFile::new(Cursor::new(data))
+    }
+}
+
 impl<R> RegionFile<R>
 where
     R: Read + Seek,
 {
     /// Parses a region file
-    pub fn new(mut r: R) -> Result<RegionFile<R>, nbt_error::Error> {
+    pub fn new(r: R) -> Result<RegionFile<R>, nbt_error::Error> {
+        RegionFile::new_with_sector_size(r, 4096)
+    }
+
+    /// Parses a region file that uses a nonstandard sector size.
+    ///
+    /// Some modified servers write regions with sectors bigger than the usual 4096 bytes.  The
+    /// header is always 8192 bytes, padded out to a whole number of sectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector_size` is zero.
+    pub fn new_with_sector_size(
+        mut r: R,
+        sector_size: u32,
+    ) -> Result<RegionFile<R>, nbt_error::Error> {
+        assert!(sector_size > 0);
+
+        // read the whole header at once, rather than one u32 at a time.  A truncated
+        // timestamp table is padded out with zeros (meaning no timestamp), so that the offsets
+        // are still usable.
+        let mut header = [0; 8192];
+        r.read_exact(&mut header[..4096])?;
+        io::copy(&mut (&mut r).take(4096), &mut &mut header[4096..])?;
+        let mut words = header
+            .chunks_exact(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]));
+
         let mut offsets = Vec::with_capacity(1024);
-        let mut timestamps = Vec::with_capacity(1024);
         let mut chunk_size = Vec::with_capacity(1024);
+        let mut present = [0; 32];
 
-        for _ in 0..1024 {
-            let v = r.read_u32::<BigEndian>()?;
-
+        for (idx, v) in words.by_ref().take(1024).enumerate() {
             // upper 3 bytes are an offset
             let offset = v >> 8;
             let sector_count = (v & 0xff) as u8;
 
-            offsets.push(offset * 4096);
+            if offset > 0 {
+                present[idx / 32] |= 1 << (idx % 32);
+            }
+            offsets.push(offset as u64 * sector_size as u64);
             chunk_size.push(sector_count);
         }
 
-        for _ in 0..1024 {
-            let ts = r.read_u32::<BigEndian>()?;
-            timestamps.push(ts);
+        let timestamps: Vec<u32> = words.collect();
+
+        // the first sectors hold the header (two of them, normally)
+        let mut used_sectors = vec![true; 8192usize.div_ceil(sector_size as usize)];
+        for (&offset, &count) in offsets.iter().zip(chunk_size.iter()) {
+            if offset == 0 {
+                continue;
+            }
+            let start = (offset / sector_size as u64) as usize;
+            let end = start + count as usize;
+            if used_sectors.len() < end {
+                used_sectors.resize(end, false);
+            }
+            for used in &mut used_sectors[start..end] {
+                *used = true;
+            }
         }
 
         Ok(RegionFile {
             offsets,
             timestamps,
             chunk_size,
+            sector_size,
+            present,
+            used_sectors,
             cursor: Box::new(r),
         })
     }
 
+    /// Parses a region file, then checks that every chunk in it can be loaded.
+    ///
+    /// Unlike `new`, which only reads the header, this reads and decompresses every chunk up
+    /// front, so it is much slower.  If any chunk fails to load, `Error::CorruptRegion` is
+    /// returned listing all of the failures.
+    pub fn new_verified(r: R) -> Result<RegionFile<R>, nbt_error::Error> {
+        let mut region = RegionFile::new(r)?;
+        region.verify()?;
+        Ok(region)
+    }
+
+    /// Parses a region file, first checking that it looks like one.
+    ///
+    /// Region files have no magic number, so `new` will happily parse any file at least 8192
+    /// bytes long.  This also checks that the file is big enough for the header, and that every
+    /// chunk in the header lies within the file, after the header.  If not,
+    /// `Error::NotARegionFile` is returned.
+    pub fn new_checked(mut r: R) -> Result<RegionFile<R>, nbt_error::Error> {
+        let len = r.seek(SeekFrom::End(0))?;
+        r.seek(SeekFrom::Start(0))?;
+        if len < 8192 {
+            return Err(nbt_error::Error::NotARegionFile);
+        }
+
+        let region = RegionFile::new(r)?;
+        // the last sector isn't always padded out
+        let sectors = len.div_ceil(4096);
+        for (&offset, &count) in region.offsets.iter().zip(region.chunk_size.iter()) {
+            if offset == 0 {
+                continue;
+            }
+            let start = offset / 4096;
+            if start < 2 || count == 0 || start + count as u64 > sectors {
+                return Err(nbt_error::Error::NotARegionFile);
+            }
+        }
+        Ok(region)
+    }
+
+    /// Checks that every chunk in this region can be loaded.
+    ///
+    /// If any chunk fails to load, a `MultiError` is returned listing all of the failures.
+    pub fn verify(&mut self) -> Result<(), nbt_error::MultiError> {
+        let mut failures = Vec::new();
+        for z in 0..32 {
+            for x in 0..32 {
+                if self.chunk_exists(x, z) {
+                    if let Err(e) = self.load_chunk(x, z) {
+                        failures.push((x, z, e));
+                    }
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(nbt_error::MultiError { errors: failures })
+        }
+    }
+
     /// Returns a unix timestamp of when a given chunk was last modified.  If the chunk does not
     /// exist in this Region, return `None`.
     ///
@@ -77,7 +270,7 @@ where
     /// # Panics
     ///
     /// x and z must be between 0 and 31 (inclusive).  If not, panics.
-    fn get_chunk_offset(&self, x: u8, z: u8) -> u32 {
+    fn get_chunk_offset(&self, x: u8, z: u8) -> u64 {
         assert!(x < 32);
         assert!(z < 32);
         let idx = x as usize % 32 + (z as usize % 32) * 32;
@@ -93,70 +286,1347 @@ where
         assert!(x < 32);
         assert!(z < 32);
         let idx = x as usize % 32 + (z as usize % 32) * 32;
-        self.offsets.get(idx).map_or(false, |v| *v > 0)
+        self.offsets.get(idx).is_some_and(|v| *v > 0)
+    }
+
+    /// Returns a hash of this region's header (chunk locations, sizes, and timestamps).
+    ///
+    /// This is stable across runs and platforms, so it can be stored and compared later to
+    /// cheaply tell whether a region has changed.  It is not a cryptographic hash.
+    pub fn header_fingerprint(&self) -> u64 {
+        // 64-bit FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        for idx in 0..1024 {
+            feed(&self.offsets[idx].to_be_bytes());
+            feed(&(self.chunk_size[idx] as u32).to_be_bytes());
+            feed(&self.timestamps[idx].to_be_bytes());
+        }
+        hash
+    }
+
+    /// Returns a bitset of which chunks exist in this Region.
+    ///
+    /// There is one u32 per row, indexed by z.  Bit x of a row is set if chunk (x, z) exists.
+    pub fn present_mask(&self) -> &[u32; 32] {
+        &self.present
+    }
+
+    /// Returns the `(x, z)` coordinates of every chunk in this region, in row (z) order.
+    pub fn present_chunks(&self) -> Vec<(u8, u8)> {
+        let mut coords = Vec::new();
+        for z in 0..32 {
+            for x in 0..32 {
+                if self.chunk_exists(x, z) {
+                    coords.push((x, z));
+                }
+            }
+        }
+        coords
+    }
+
+    /// Returns an iterator over every chunk in this region, as `(x, z, chunk)`.
+    ///
+    /// Chunks are loaded lazily, in row order (z, then x).  A chunk that fails to load is
+    /// yielded as an `Err`, and iteration carries on with the next chunk, so one corrupt chunk
+    /// doesn't hide the rest of the region.
+    pub fn chunks(&mut self) -> ChunkIter<'_, R> {
+        let coords = self.present_chunks();
+        ChunkIter {
+            region: self,
+            coords: coords.into_iter(),
+        }
+    }
+
+    /// Like `chunks`, but visits chunks in Z-order (the order of their Morton codes).
+    ///
+    /// This keeps chunks that are near each other close together in the iteration order,
+    /// which helps when processing a chunk also needs its neighbors.
+    pub fn chunks_zorder(&mut self) -> ChunkIter<'_, R> {
+        let mut coords = self.present_chunks();
+        coords.sort_by_key(|&(x, z)| morton(x, z));
+        ChunkIter {
+            region: self,
+            coords: coords.into_iter(),
+        }
+    }
+
+    /// Like `chunks`, but only visits chunks with a timestamp of at least `ts`.
+    ///
+    /// Timestamps are checked in the header, so older chunks aren't loaded at all.
+    pub fn chunks_modified_since(&mut self, ts: u32) -> ChunkIter<'_, R> {
+        let mut coords = self.present_chunks();
+        coords.retain(|&(x, z)| self.get_chunk_timestamp(x, z).is_some_and(|t| t >= ts));
+        ChunkIter {
+            region: self,
+            coords: coords.into_iter(),
+        }
+    }
+
+    /// Finds room for `count` consecutive sectors and marks them as used, returning the first
+    /// sector's index.
+    ///
+    /// The first free run of sectors that is large enough is used.  If there isn't one, the
+    /// sectors are allocated at the end of the file.
+    ///
+    /// # Panics
+    ///
+    /// count must be at least 1.  If not, panics.
+    pub fn allocate_sectors(&mut self, count: usize) -> u32 {
+        assert!(count > 0);
+        let mut start = 0;
+        let mut run = 0;
+        for (idx, &used) in self.used_sectors.iter().enumerate() {
+            if used {
+                run = 0;
+            } else {
+                if run == 0 {
+                    start = idx;
+                }
+                run += 1;
+                if run == count {
+                    break;
+                }
+            }
+        }
+
+        if run < count {
+            // not enough room, so grow the file (reusing any free sectors at the very end)
+            let end = self.used_sectors.len();
+            if run == 0 || start + run != end {
+                start = end;
+            }
+            self.used_sectors.resize(start + count, false);
+        }
+
+        for used in &mut self.used_sectors[start..start + count] {
+            *used = true;
+        }
+        start as u32
     }
 
     /// Loads a chunk into a parsed NBT Tag structure.
     ///
+    /// Both zlib (used by Anvil) and gzip (used by some McRegion files) compressed chunks are
+    /// supported, as well as uncompressed chunks (compression type 3).
+    ///
+    /// This leaves the underlying reader positioned just after the chunk's data.  Use
+    /// `load_chunk_preserving` if something else relies on the reader's position.
+    ///
     /// # Panics
     ///
     /// x and z must be between 0 and 31 (inclusive).  If not, panics.
     pub fn load_chunk(&mut self, x: u8, z: u8) -> Result<nbt::Tag, nbt_error::Error> {
+        let (_, tag) = self.load_chunk_named(x, z)?;
+        Ok(tag)
+    }
+
+    /// Loads a chunk like `load_chunk`, but also returns the name of the chunk's root tag.
+    ///
+    /// For Anvil chunks this is normally the empty string.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn load_chunk_named(
+        &mut self,
+        x: u8,
+        z: u8,
+    ) -> Result<(String, nbt::Tag), nbt_error::Error> {
+        self.read_chunk_data(x, z)
+            .and_then(|(compression_type, data)| decode_chunk(compression_type, &data))
+            .map_err(|e| chunk_load_error(x, z, e))
+    }
+
+    /// Reads and decompresses a chunk, returning its NBT data without parsing it.
+    ///
+    /// Like `load_chunk`, gzip, zlib, and uncompressed chunks are supported.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn load_chunk_raw(&mut self, x: u8, z: u8) -> Result<Vec<u8>, nbt_error::Error> {
+        self.read_chunk_data(x, z)
+            .and_then(|(compression_type, data)| decompress(compression_type, &data))
+            .map_err(|e| chunk_load_error(x, z, e))
+    }
+
+    /// Loads a chunk like `load_chunk`, but parses it with the given options.
+    ///
+    /// This is useful for applying stricter limits (like `max_depth`) to untrusted chunks.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn load_chunk_with_options(
+        &mut self,
+        x: u8,
+        z: u8,
+        opts: &nbt::ParseOptions,
+    ) -> Result<nbt::Tag, nbt_error::Error> {
+        let raw = self.load_chunk_raw(x, z)?;
+        nbt::Tag::parse_with_options(&mut Cursor::new(raw), opts)
+            .map(|(_, tag)| tag)
+            .map_err(|e| chunk_load_error(x, z, e))
+    }
+
+    /// Loads a chunk like `load_chunk`, but restores the underlying reader to its original
+    /// position afterwards (even if loading fails).
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn load_chunk_preserving(&mut self, x: u8, z: u8) -> Result<nbt::Tag, nbt_error::Error> {
+        let pos = self.cursor.stream_position()?;
+        let result = self.load_chunk(x, z);
+        self.cursor.seek(SeekFrom::Start(pos))?;
+        result
+    }
+
+    /// Loads a chunk like `load_chunk`, but if the chunk can't be decompressed using its
+    /// declared compression type, tries the other types (gzip, zlib, then uncompressed) before
+    /// giving up.
+    ///
+    /// This can salvage chunks whose compression byte has been corrupted.  Only decompression
+    /// errors are retried; a chunk that decompresses but doesn't parse fails straight away.  If
+    /// every attempt fails, the error from the declared compression type is returned.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn load_chunk_resilient(&mut self, x: u8, z: u8) -> Result<nbt::Tag, nbt_error::Error> {
+        let (compression_type, data) = self
+            .read_chunk_data(x, z)
+            .map_err(|e| chunk_load_error(x, z, e))?;
+
+        let (_, tag) = match decode_chunk(compression_type, &data) {
+            Err(err @ nbt_error::Error::Decompression { .. })
+            | Err(err @ nbt_error::Error::UnsupportedCompressionFormat { .. }) => [1, 2, 3]
+                .iter()
+                .filter(|&&other| other != compression_type)
+                .find_map(|&other| decode_chunk(other, &data).ok())
+                .ok_or(err),
+            result => result,
+        }
+        .map_err(|e| chunk_load_error(x, z, e))?;
+        Ok(tag)
+    }
+
+    /// Returns the generation status of a chunk, like `full` or `features`.
+    ///
+    /// This loads the whole chunk, and reads `Status` (or `Level/Status` before 1.18).  Returns
+    /// `None` if the chunk doesn't exist or has no status, which is the case for chunks from
+    /// before 1.13.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn chunk_status(&mut self, x: u8, z: u8) -> Result<Option<String>, nbt_error::Error> {
+        if !self.chunk_exists(x, z) {
+            return Ok(None);
+        }
+        let chunk = self.load_chunk(x, z)?;
+        let status = (&chunk)
+            .key("Status")
+            .or_else(|| chunk.key("Level").key("Status"))
+            .as_string()
+            .cloned();
+        Ok(status)
+    }
+
+    /// Returns the ratio of decompressed to compressed size, over all chunks in this region.
+    ///
+    /// This decompresses every chunk, so is fairly slow.  The compressed size doesn't include
+    /// the length prefix, compression type, or padding.  A region with no chunks has a ratio
+    /// of 1.
+    pub fn compression_ratio(&mut self) -> Result<f64, nbt_error::Error> {
+        let mut compressed = 0;
+        let mut decompressed = 0;
+        for z in 0..32 {
+            for x in 0..32 {
+                if self.chunk_exists(x, z) {
+                    let (compression_type, data) = self
+                        .read_chunk_data(x, z)
+                        .map_err(|e| chunk_load_error(x, z, e))?;
+                    compressed += data.len();
+                    decompressed += decompress(compression_type, &data)
+                        .map_err(|e| chunk_load_error(x, z, e))?
+                        .len();
+                }
+            }
+        }
+
+        if compressed == 0 {
+            return Ok(1.0);
+        }
+        Ok(decompressed as f64 / compressed as f64)
+    }
+
+    /// Returns the coordinates of every chunk that is stored externally, in row (z) order.
+    ///
+    /// Chunks too big to fit in a region are written to a separate `c.<x>.<z>.mcc` file, and
+    /// marked by setting the high bit of their compression type.  This reads the compression
+    /// type of every chunk, but not the chunks themselves.
+    pub fn external_chunks(&mut self) -> Result<Vec<(u8, u8)>, nbt_error::Error> {
+        let mut external = Vec::new();
+        for (x, z) in self.present_chunks() {
+            let offset = self.get_chunk_offset(x, z);
+            // skip the length prefix
+            let compression_type = self
+                .cursor
+                .seek(SeekFrom::Start(offset + 4))
+                .and_then(|_| self.cursor.read_u8())
+                .map_err(|e| chunk_load_error(x, z, e.into()))?;
+            if compression_type & 0x80 != 0 {
+                external.push((x, z));
+            }
+        }
+        Ok(external)
+    }
+
+    /// Returns the fraction of allocated chunk sectors that are actually needed to hold the
+    /// chunks' data.
+    ///
+    /// For each chunk, the sectors needed for its length prefix, compression type, and data
+    /// are compared with the sector count in the header.  A value of 1 means no chunk has more
+    /// sectors than it needs, which is also the result for a region with no chunks.  Only the
+    /// length prefix of each chunk is read.
+    pub fn sector_utilization(&mut self) -> Result<f64, nbt_error::Error> {
+        let mut needed = 0;
+        let mut allocated = 0;
+        for (x, z) in self.present_chunks() {
+            let offset = self.get_chunk_offset(x, z);
+            let len = self
+                .cursor
+                .seek(SeekFrom::Start(offset))
+                .and_then(|_| self.cursor.read_u32::<BigEndian>())
+                .map_err(|e| chunk_load_error(x, z, e.into()))? as u64
+                + 4;
+
+            needed += len.div_ceil(self.sector_size as u64);
+            allocated += self.chunk_size[x as usize + z as usize * 32] as u64;
+        }
+
+        if allocated == 0 {
+            return Ok(1.0);
+        }
+        Ok(needed as f64 / allocated as f64)
+    }
+
+    /// Copies this region to `out`, leaving off any unused sectors at the end of the file.
+    ///
+    /// Everything up to the end of the last chunk is copied as-is, so unused sectors in the
+    /// middle of the file are kept.  If the file ends before the last chunk's final sector is
+    /// padded out, only what's there is copied.  Returns the number of bytes written.
+    pub fn shrink<W>(&mut self, out: &mut W) -> Result<u64, nbt_error::Error>
+    where
+        W: Write,
+    {
+        let end = self
+            .offsets
+            .iter()
+            .zip(self.chunk_size.iter())
+            .filter(|&(&offset, _)| offset > 0)
+            .map(|(&offset, &count)| offset + count as u64 * self.sector_size as u64)
+            .max()
+            .unwrap_or(0)
+            .max(8192);
+
+        self.cursor.seek(SeekFrom::Start(0))?;
+        Ok(io::copy(&mut (&mut self.cursor).take(end), out)?)
+    }
+
+    /// Returns the exact on-disk bytes of every sector allocated to a chunk, or `None` if the
+    /// chunk doesn't exist.
+    ///
+    /// This includes the length prefix, compression type, and any padding after the chunk
+    /// data, which makes it useful for debugging framing problems.  If the file ends partway
+    /// through the chunk's sectors, only what's there is returned.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn dump_chunk_sectors(
+        &mut self,
+        x: u8,
+        z: u8,
+    ) -> Result<Option<Vec<u8>>, nbt_error::Error> {
+        if !self.chunk_exists(x, z) {
+            return Ok(None);
+        }
+        let offset = self.get_chunk_offset(x, z);
+        let idx = x as usize + z as usize * 32;
+
+        let len = self.chunk_size[idx] as u64 * self.sector_size as u64;
+        let mut v = Vec::new();
+        self.cursor
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| (&mut self.cursor).take(len).read_to_end(&mut v))
+            .map_err(|e| chunk_load_error(x, z, e.into()))?;
+        Ok(Some(v))
+    }
+
+    /// Reads the raw (still compressed) data for a chunk, along with its compression type.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    fn read_chunk_data(&mut self, x: u8, z: u8) -> Result<(u8, Vec<u8>), nbt_error::Error> {
         let offset = self.get_chunk_offset(x, z); // might panic
+        let idx = x as usize + z as usize * 32;
 
-        self.cursor.seek(SeekFrom::Start(offset as u64))?;
+        self.cursor.seek(SeekFrom::Start(offset))?;
         let total_len = self.cursor.read_u32::<BigEndian>()? as usize;
-        let compression_type = self.cursor.read_u8()?;
-
-        if compression_type != 2 {
-            return Err(nbt_error::Error::UnsupportedCompressionFormat { compression_type });
+        // the length prefix comes from the file, so check it before allocating anything
+        if total_len + 4 > self.chunk_size[idx] as usize * self.sector_size as usize {
+            return Err(nbt_error::Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "chunk is longer than the sectors allocated to it",
+            )));
         }
+        let compression_type = self.cursor.read_u8()?;
 
         let compressed_data = {
-            let mut v = vec![0; total_len - 1];
+            let mut v = vec![0; total_len.saturating_sub(1)];
             self.cursor.read_exact(&mut v)?;
             v
         };
 
-        let mut decoder = flate2::read::ZlibDecoder::new(Cursor::new(compressed_data));
+        Ok((compression_type, compressed_data))
+    }
+}
 
-        let (_, tag) = nbt::Tag::parse(&mut decoder).unwrap();
-        Ok(tag)
+impl RegionFile<File> {
+    /// Opens a region file for both reading and writing, so that chunks can be written back in
+    /// place with `write_chunk` and `remove_chunk`.
+    pub fn open_rw<P>(path: P) -> Result<RegionFile<File>, nbt_error::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let f = OpenOptions::new().read(true).write(true).open(path)?;
+        RegionFile::new(f)
     }
 }
 
-#[test]
-fn test_region() {
-    // The values used in the assertions in this test were gotten from the nbt.py impl in
-    // Minecraft-Overviewer
-    use nbt::Taglike;
-    use std::fs::File;
+impl<R> RegionFile<R>
+where
+    R: Read + Write + Seek,
+{
+    /// Writes a chunk, replacing the existing one if there is one.
+    ///
+    /// The chunk is zlib compressed and written into the first free run of sectors big enough
+    /// to hold it (which may be where the old chunk was), and its header entry is updated in
+    /// place with a new location and the current time.  Chunks that need more than 255
+    /// sectors have to be stored externally, which isn't supported, so they're an error.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn write_chunk(&mut self, x: u8, z: u8, tag: &nbt::Tag) -> Result<(), nbt_error::Error> {
+        let idx = x as usize + z as usize * 32;
+        let mut e = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        tag.write(&mut e, "")?;
+        let compressed = e.finish()?;
 
-    let f = File::open("tests/data/r.0.0.mca").unwrap();
-    let mut region = RegionFile::new(f).unwrap();
+        let mut data = Vec::with_capacity(compressed.len() + 5);
+        data.write_u32::<BigEndian>(compressed.len() as u32 + 1)?;
+        data.write_u8(2)?;
+        data.extend_from_slice(&compressed);
+        let sector_size = self.sector_size as usize;
+        let sector_count = data.len().div_ceil(sector_size);
+        if sector_count > 255 {
+            return Err(nbt_error::Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk is too big to store in a region",
+            )));
+        }
+        data.resize(sector_count * sector_size, 0);
 
-    let ts = region.get_chunk_timestamp(0, 0).unwrap();
-    assert_eq!(ts, 1383443712);
+        self.free_sectors(idx);
+        let sector = self.allocate_sectors(sector_count);
+        let offset = sector as u64 * self.sector_size as u64;
+        self.cursor.seek(SeekFrom::Start(offset))?;
+        self.cursor.write_all(&data)?;
 
-    let ts = region.get_chunk_timestamp(13, 23);
-    assert_eq!(ts, None);
-    assert!(!region.chunk_exists(13, 23));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        self.write_header_entry(idx, sector << 8 | sector_count as u32, timestamp)?;
+        self.offsets[idx] = offset;
+        self.chunk_size[idx] = sector_count as u8;
+        self.timestamps[idx] = timestamp;
+        self.present[z as usize] |= 1 << x;
+        Ok(())
+    }
 
-    let ts = region.get_chunk_timestamp(14, 10).unwrap();
-    assert_eq!(ts, 1383443713);
+    /// Removes a chunk, if it exists.
+    ///
+    /// Its header entry is cleared in place, and its sectors are freed for reuse, but the file
+    /// isn't shrunk.  See `shrink` for that.
+    ///
+    /// # Panics
+    ///
+    /// x and z must be between 0 and 31 (inclusive).  If not, panics.
+    pub fn remove_chunk(&mut self, x: u8, z: u8) -> Result<(), nbt_error::Error> {
+        if !self.chunk_exists(x, z) {
+            return Ok(());
+        }
+        let idx = x as usize + z as usize * 32;
+        self.free_sectors(idx);
+        self.write_header_entry(idx, 0, 0)?;
+        self.offsets[idx] = 0;
+        self.chunk_size[idx] = 0;
+        self.timestamps[idx] = 0;
+        self.present[z as usize] &= !(1 << x);
+        Ok(())
+    }
 
-    assert!(region.chunk_exists(14, 10));
-    assert!(!region.chunk_exists(15, 15));
+    /// Writes the location and timestamp of a chunk to the header
+    fn write_header_entry(
+        &mut self,
+        idx: usize,
+        location: u32,
+        timestamp: u32,
+    ) -> Result<(), nbt_error::Error> {
+        self.cursor.seek(SeekFrom::Start(idx as u64 * 4))?;
+        self.cursor.write_u32::<BigEndian>(location)?;
+        self.cursor.seek(SeekFrom::Start(4096 + idx as u64 * 4))?;
+        self.cursor.write_u32::<BigEndian>(timestamp)?;
+        Ok(())
+    }
 
-    assert_eq!(region.get_chunk_offset(0, 0), 180224);
+    /// Marks the sectors used by a chunk as free
+    fn free_sectors(&mut self, idx: usize) {
+        if self.offsets[idx] == 0 {
+            return;
+        }
+        let start = (self.offsets[idx] / self.sector_size as u64) as usize;
+        let end = (start + self.chunk_size[idx] as usize).min(self.used_sectors.len());
+        for used in &mut self.used_sectors[start..end] {
+            *used = false;
+        }
+    }
+}
+
+/// An iterator over the chunks in a region
+///
+/// This is created by `RegionFile::chunks`.
+pub struct ChunkIter<'a, R> {
+    region: &'a mut RegionFile<R>,
+    coords: std::vec::IntoIter<(u8, u8)>,
+}
+
+impl<'a, R> Iterator for ChunkIter<'a, R>
+where
+    R: Read + Seek,
+{
+    type Item = (u8, u8, Result<nbt::Tag, nbt_error::Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (x, z) = self.coords.next()?;
+        Some((x, z, self.region.load_chunk(x, z)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.coords.size_hint()
+    }
+}
+
+/// Interleaves the bits of chunk coordinates (x in the even bits, z in the odd bits)
+fn morton(x: u8, z: u8) -> u16 {
+    let spread = |v: u8| {
+        let mut out = 0u16;
+        for bit in 0..8 {
+            out |= ((v as u16 >> bit) & 1) << (bit * 2);
+        }
+        out
+    };
+    spread(x) | spread(z) << 1
+}
+
+/// Wraps an error with the coordinates of the chunk it happened in
+fn chunk_load_error(x: u8, z: u8, source: nbt_error::Error) -> nbt_error::Error {
+    nbt_error::Error::ChunkLoad {
+        x,
+        z,
+        source: Box::new(source),
+    }
+}
+
+/// Decompresses chunk data according to its compression type
+///
+/// Type 1 is gzip, type 2 is zlib, and type 3 is uncompressed.
+fn decompress(compression_type: u8, data: &[u8]) -> Result<Vec<u8>, nbt_error::Error> {
+    let corrupt = |source| nbt_error::Error::Decompression {
+        compression_type,
+        source,
+    };
+    let mut v = Vec::new();
+    match compression_type {
+        1 => GzDecoder::new(data).read_to_end(&mut v).map_err(corrupt)?,
+        2 => ZlibDecoder::new(data)
+            .read_to_end(&mut v)
+            .map_err(corrupt)?,
+        3 => {
+            v.extend_from_slice(data);
+            data.len()
+        }
+        _ => return Err(nbt_error::Error::UnsupportedCompressionFormat { compression_type }),
+    };
+    Ok(v)
+}
+
+/// Decompresses and parses chunk data according to its compression type
+fn decode_chunk(compression_type: u8, data: &[u8]) -> Result<(String, nbt::Tag), nbt_error::Error> {
+    let raw = decompress(compression_type, data)?;
+    nbt::Tag::parse(&mut Cursor::new(raw))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds an in-memory region file out of `(x, z, compression_type, payload)` entries
+    fn build_region(chunks: &[(u8, u8, u8, &[u8])]) -> Vec<u8> {
+        build_region_with_sector_size(chunks, 4096)
+    }
+
+    fn build_region_with_sector_size(
+        chunks: &[(u8, u8, u8, &[u8])],
+        sector_size: usize,
+    ) -> Vec<u8> {
+        let header_sectors = 8192usize.div_ceil(sector_size);
+        let mut header = vec![0; header_sectors * sector_size];
+        let mut body = Vec::new();
+
+        for &(x, z, compression_type, payload) in chunks {
+            let idx = x as usize + z as usize * 32;
+            let sector = header_sectors + body.len() / sector_size;
+
+            let mut chunk = (payload.len() as u32 + 1).to_be_bytes().to_vec();
+            chunk.push(compression_type);
+            chunk.extend_from_slice(payload);
+            let sector_count = chunk.len().div_ceil(sector_size);
+            chunk.resize(sector_count * sector_size, 0);
+
+            let location = ((sector << 8) | sector_count) as u32;
+            header[idx * 4..idx * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            header[4096 + idx * 4..4096 + idx * 4 + 4].copy_from_slice(&1u32.to_be_bytes());
+            body.extend_from_slice(&chunk);
+        }
+
+        header.extend_from_slice(&body);
+        header
+    }
+
+    fn level_dat() -> (Vec<u8>, nbt::Tag) {
+        let data = std::fs::read("tests/data/level.dat").unwrap();
+        let (_, tag) = nbt::Tag::parse(&mut GzDecoder::new(&data[..])).unwrap();
+        (data, tag)
+    }
+
+    #[test]
+    fn test_region() {
+        // The values used in the assertions in this test were gotten from the nbt.py impl in
+        // Minecraft-Overviewer
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+
+        let ts = region.get_chunk_timestamp(0, 0).unwrap();
+        assert_eq!(ts, 1383443712);
+
+        let ts = region.get_chunk_timestamp(13, 23);
+        assert_eq!(ts, None);
+        assert!(!region.chunk_exists(13, 23));
+
+        let ts = region.get_chunk_timestamp(14, 10).unwrap();
+        assert_eq!(ts, 1383443713);
+
+        assert!(region.chunk_exists(14, 10));
+        assert!(!region.chunk_exists(15, 15));
+
+        assert_eq!(region.get_chunk_offset(0, 0), 180224);
+
+        let tag = region.load_chunk(0, 0).unwrap();
+        // tag.pretty_print(0, None);
+
+        let (name, named_tag) = region.load_chunk_named(0, 0).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(named_tag, tag);
+
+        let level = tag.key("Level").unwrap();
+        let last_update = level.key("LastUpdate").as_i64().unwrap();
+        let z_pos = level.key("zPos").as_i32().unwrap();
+        assert_eq!(last_update, 137577);
+        assert_eq!(z_pos, 0);
+        level.pretty_print(0, None);
+    }
+
+    #[test]
+    fn test_present_mask() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let region = RegionFile::new(f).unwrap();
+        let mask = region.present_mask();
+
+        for z in 0..32 {
+            for x in 0..32 {
+                let bit = mask[z as usize] & (1 << x) != 0;
+                assert_eq!(bit, region.chunk_exists(x, z), "chunk ({}, {})", x, z);
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_verified() {
+        let (data, _) = level_dat();
+        let good = build_region(&[(0, 0, 1, &data)]);
+        assert!(RegionFile::new_verified(Cursor::new(good)).is_ok());
+
+        let bad = build_region(&[(0, 0, 1, &data), (5, 7, 2, b"not zlib data")]);
+        // opening lazily doesn't notice the bad chunk
+        let mut region = RegionFile::new(Cursor::new(bad.clone())).unwrap();
+        let failures = region.verify().unwrap_err();
+        assert_eq!(failures.errors.len(), 1);
+        assert_eq!((failures.errors[0].0, failures.errors[0].1), (5, 7));
+
+        match RegionFile::new_verified(Cursor::new(bad)) {
+            Err(nbt_error::Error::CorruptRegion(e)) => assert_eq!(e, failures),
+            _ => panic!("expected a CorruptRegion error"),
+        }
+    }
+
+    #[test]
+    fn test_load_chunk_raw() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let raw = region.load_chunk_raw(1, 12).unwrap();
+        let (_, tag) = nbt::Tag::parse(&mut &raw[..]).unwrap();
+        assert_eq!(tag, region.load_chunk(1, 12).unwrap());
+
+        let (data, expected) = level_dat();
+        let region = build_region(&[(0, 0, 1, &data), (1, 0, 3, b"raw"), (2, 0, 4, b"lz4")]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        let raw = region.load_chunk_raw(0, 0).unwrap();
+        assert_eq!(nbt::Tag::parse(&mut &raw[..]).unwrap().1, expected);
+        assert_eq!(region.load_chunk_raw(1, 0).unwrap(), b"raw");
+        assert_eq!(
+            region.load_chunk_raw(2, 0),
+            Err(chunk_load_error(
+                2,
+                0,
+                nbt_error::Error::UnsupportedCompressionFormat {
+                    compression_type: 4
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_chunk_with_options() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+
+        let opts = nbt::ParseOptions::default();
+        let chunk = region.load_chunk_with_options(1, 12, &opts).unwrap();
+        assert_eq!(chunk, region.load_chunk(1, 12).unwrap());
+
+        // the root compound and Level are fine, but nothing inside Level is
+        let opts = nbt::ParseOptions {
+            max_depth: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            region.load_chunk_with_options(1, 12, &opts),
+            Err(chunk_load_error(
+                1,
+                12,
+                nbt_error::Error::DepthLimitExceeded
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_chunk_compression_types() {
+        let (gz, expected) = level_dat();
+        let mut raw = Vec::new();
+        expected.write(&mut raw, "").unwrap();
+        let mut zlib = Vec::new();
+        expected
+            .write_compressed(&mut zlib, "", nbt::Compression::Zlib)
+            .unwrap();
+
+        let region = build_region(&[
+            (0, 0, 1, &gz),
+            (1, 0, 2, &zlib),
+            (2, 0, 3, &raw),
+            (3, 0, 7, &raw),
+        ]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        for x in 0..3 {
+            assert_eq!(region.load_chunk(x, 0).unwrap(), expected);
+        }
+        assert_eq!(
+            region.load_chunk(3, 0),
+            Err(chunk_load_error(
+                3,
+                0,
+                nbt_error::Error::UnsupportedCompressionFormat {
+                    compression_type: 7
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_chunk_preserving() {
+        let (data, expected) = level_dat();
+        let region = build_region(&[(0, 0, 1, &data)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        region.cursor.seek(SeekFrom::Start(1234)).unwrap();
+        assert_eq!(region.load_chunk_preserving(0, 0).unwrap(), expected);
+        assert_eq!(region.cursor.stream_position().unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_allocate_sectors() {
+        let mut data = build_region(&[
+            (0, 0, 2, &[0; 5000]),
+            (1, 0, 2, &[0; 5000]),
+            (2, 0, 2, &[0; 10]),
+        ]);
+        // remove chunk (1, 0), leaving a two sector gap at sectors 4 and 5
+        data[4..8].copy_from_slice(&[0; 4]);
+        let mut region = RegionFile::new(Cursor::new(data)).unwrap();
+
+        // too big for the gap, so it goes on the end
+        assert_eq!(region.allocate_sectors(3), 7);
+        assert_eq!(region.allocate_sectors(2), 4);
+        assert_eq!(region.allocate_sectors(1), 10);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_allocate_no_sectors() {
+        let mut region = RegionFile::new(Cursor::new(build_region(&[]))).unwrap();
+        region.allocate_sectors(0);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let ratio = region.compression_ratio().unwrap();
+        assert!(ratio > 1.0);
+    }
+
+    #[test]
+    fn test_chunks_continue_after_error() {
+        let (data, expected) = level_dat();
+        let region = build_region(&[
+            (0, 0, 1, &data),
+            (1, 0, 2, b"not zlib data"),
+            (2, 0, 1, &data),
+        ]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        let chunks: Vec<_> = region.chunks().collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!((chunks[0].0, chunks[0].1), (0, 0));
+        assert_eq!(chunks[0].2.as_ref().unwrap(), &expected);
+        assert_eq!((chunks[1].0, chunks[1].1), (1, 0));
+        assert!(chunks[1].2.is_err());
+        assert_eq!((chunks[2].0, chunks[2].1), (2, 0));
+        assert_eq!(chunks[2].2.as_ref().unwrap(), &expected);
+    }
+
+    #[test]
+    fn test_chunks_zorder() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+
+        let zorder: Vec<(u8, u8)> = region.chunks_zorder().map(|(x, z, _)| (x, z)).collect();
+        let mut rows: Vec<(u8, u8)> = region.chunks().map(|(x, z, _)| (x, z)).collect();
+        assert_ne!(zorder, rows);
+        assert!(zorder
+            .windows(2)
+            .all(|w| morton(w[0].0, w[0].1) < morton(w[1].0, w[1].1)));
+
+        rows.sort_by_key(|&(x, z)| morton(x, z));
+        assert_eq!(zorder, rows);
+
+        assert_eq!(morton(1, 0), 1);
+        assert_eq!(morton(0, 1), 2);
+        assert_eq!(morton(3, 3), 15);
+        assert_eq!(morton(31, 31), 1023);
+    }
+
+    #[test]
+    fn test_chunks_modified_since() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+
+        let mut timestamps: Vec<u32> = region
+            .present_chunks()
+            .into_iter()
+            .map(|(x, z)| region.get_chunk_timestamp(x, z).unwrap())
+            .collect();
+        timestamps.sort();
+        let threshold = timestamps[timestamps.len() / 2];
+        let expected = timestamps.iter().filter(|&&t| t >= threshold).count();
+        assert!(expected < timestamps.len());
+
+        let coords: Vec<(u8, u8)> = region
+            .chunks_modified_since(threshold)
+            .map(|(x, z, chunk)| {
+                assert!(chunk.is_ok());
+                (x, z)
+            })
+            .collect();
+        assert_eq!(coords.len(), expected);
+        for (x, z) in coords {
+            assert!(region.get_chunk_timestamp(x, z).unwrap() >= threshold);
+        }
+        assert_eq!(region.chunks_modified_since(u32::MAX).count(), 0);
+    }
+
+    #[test]
+    fn test_header_fingerprint() {
+        use std::fs::File;
+
+        let a = RegionFile::new(File::open("tests/data/r.0.0.mca").unwrap()).unwrap();
+        let mut b = RegionFile::new(File::open("tests/data/r.0.0.mca").unwrap()).unwrap();
+        assert_eq!(a.header_fingerprint(), b.header_fingerprint());
+
+        b.timestamps[0] += 1;
+        assert_ne!(a.header_fingerprint(), b.header_fingerprint());
+
+        // offsets past 4 GiB are hashed in full
+        let mut c = RegionFile::new(File::open("tests/data/r.0.0.mca").unwrap()).unwrap();
+        c.offsets[0] += 1 << 32;
+        assert_ne!(a.header_fingerprint(), c.header_fingerprint());
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let data = std::fs::read("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(Cursor::new(data)).unwrap();
+        let mut clone = region.try_clone().unwrap();
+
+        let a = clone.load_chunk(14, 10).unwrap();
+        let b = region.load_chunk(0, 0).unwrap();
+        assert_eq!(a.key("Level").key("xPos").as_i32(), Some(14));
+        assert_eq!(b.key("Level").key("xPos").as_i32(), Some(0));
+        assert_eq!(clone.load_chunk(0, 0).unwrap(), b);
+    }
+
+    #[test]
+    fn test_shrink() {
+        let (data, expected) = level_dat();
+        let mut bytes = build_region(&[(0, 0, 1, &data), (1, 0, 1, &data)]);
+        // remove chunk (1, 0), which is last in the file
+        bytes[4..8].copy_from_slice(&[0; 4]);
+        let original_len = bytes.len() as u64;
+        let mut region = RegionFile::new(Cursor::new(bytes)).unwrap();
+
+        let mut out = Vec::new();
+        let len = region.shrink(&mut out).unwrap();
+        assert_eq!(len, out.len() as u64);
+        assert_eq!(len, original_len - 4096);
+
+        let mut shrunk = RegionFile::new(Cursor::new(out)).unwrap();
+        assert_eq!(shrunk.load_chunk(0, 0).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_shrink_unpadded() {
+        // the last chunk's final sector doesn't need to be padded out on disk
+        let (data, expected) = level_dat();
+        let mut bytes = build_region(&[(0, 0, 1, &data)]);
+        bytes.truncate(8192 + 5 + data.len());
+        let mut region = RegionFile::new(Cursor::new(bytes.clone())).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(region.shrink(&mut out).unwrap(), bytes.len() as u64);
+        assert_eq!(out, bytes);
+
+        let mut shrunk = RegionFile::new(Cursor::new(out)).unwrap();
+        assert_eq!(shrunk.load_chunk(0, 0).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_header_parse() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let region = RegionFile::new(f).unwrap();
+
+        // compare against reading the header one entry at a time
+        let mut f = File::open("tests/data/r.0.0.mca").unwrap();
+        for idx in 0..1024 {
+            let v = f.read_u32::<BigEndian>().unwrap();
+            assert_eq!(region.offsets[idx], (v >> 8) as u64 * 4096);
+            assert_eq!(region.chunk_size[idx], (v & 0xff) as u8);
+        }
+        for idx in 0..1024 {
+            assert_eq!(region.timestamps[idx], f.read_u32::<BigEndian>().unwrap());
+        }
+        assert_eq!(region.get_chunk_offset(0, 0), 180224);
+    }
+
+    #[test]
+    fn test_chunk_status() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::collections::HashMap;
+
+        let zlib = |tag: nbt::Tag| {
+            let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
+            tag.write(&mut e, "").unwrap();
+            e.finish().unwrap()
+        };
+        let status = |s: &str| {
+            let mut m = HashMap::new();
+            m.insert("Status".to_string(), nbt::Tag::TagString(s.to_string()));
+            nbt::Tag::TagCompound(m)
+        };
+        let mut old = HashMap::new();
+        old.insert("Level".to_string(), status("features"));
+
+        let new = zlib(status("full"));
+        let old = zlib(nbt::Tag::TagCompound(old));
+        let region = build_region(&[(0, 0, 2, &new), (1, 0, 2, &old)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        assert_eq!(region.chunk_status(0, 0).unwrap().unwrap(), "full");
+        assert_eq!(region.chunk_status(1, 0).unwrap().unwrap(), "features");
+        assert_eq!(region.chunk_status(2, 0).unwrap(), None);
+
+        // chunks from before 1.13 don't have a status
+        let f = std::fs::File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        assert_eq!(region.chunk_status(0, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sector_size() {
+        let (data, expected) = level_dat();
+        let region = build_region_with_sector_size(&[(0, 0, 1, &data), (3, 1, 1, &data)], 8192);
+        let mut region = RegionFile::new_with_sector_size(Cursor::new(region), 8192).unwrap();
+
+        // the header takes up one sector, and each chunk another
+        assert_eq!(region.get_chunk_offset(0, 0), 8192);
+        assert_eq!(region.get_chunk_offset(3, 1), 2 * 8192);
+        assert_eq!(region.load_chunk(3, 1).unwrap(), expected);
+        assert_eq!(
+            region.dump_chunk_sectors(0, 0).unwrap().unwrap().len(),
+            8192
+        );
+        assert_eq!(region.allocate_sectors(1), 3);
+    }
+
+    #[test]
+    fn test_present_chunks() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let region = RegionFile::new(f).unwrap();
+
+        let present = region.present_chunks();
+        assert_eq!(present.len(), 381);
+        assert!(present.contains(&(0, 0)));
+        assert!(present.contains(&(14, 10)));
+        assert!(!present.contains(&(13, 23)));
+    }
+
+    #[test]
+    fn test_new_gz() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let data = std::fs::read("tests/data/r.0.0.mca").unwrap();
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let gz = e.finish().unwrap();
+
+        let mut region = RegionFile::new_gz(&gz[..]).unwrap();
+        let chunk = region.load_chunk(1, 12).unwrap();
+        assert_eq!(chunk.key("Level").key("zPos").as_i32(), Some(12));
+    }
+
+    #[test]
+    fn test_from_zip_entry() {
+        use flate2::read::DeflateDecoder;
+        use flate2::write::DeflateEncoder;
+        use flate2::Compression;
+
+        // a deflated zip entry is a raw deflate stream, read through a decoder like this
+        let data = std::fs::read("tests/data/r.0.0.mca").unwrap();
+        let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let deflated = e.finish().unwrap();
+
+        let mut region = RegionFile::from_zip_entry(DeflateDecoder::new(&deflated[..])).unwrap();
+        let chunk = region.load_chunk(1, 12).unwrap();
+        assert_eq!(chunk.key("Level").key("zPos").as_i32(), Some(12));
+    }
+
+    #[test]
+    fn test_per_chunk_errors_have_coordinates() {
+        let (data, _) = level_dat();
+        let mut bytes = build_region(&[(0, 0, 1, &data), (6, 2, 1, &data)]);
+        // cut the region off just after the start of the second chunk
+        let offset = RegionFile::from_slice(&bytes)
+            .unwrap()
+            .get_chunk_offset(6, 2);
+        bytes.truncate(offset as usize + 2);
+
+        let eof = || chunk_load_error(6, 2, io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        let mut region = RegionFile::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(region.compression_ratio(), Err(eof()));
+        assert_eq!(region.external_chunks(), Err(eof()));
+        assert_eq!(region.sector_utilization(), Err(eof()));
+    }
+
+    #[test]
+    fn test_sector_utilization() {
+        use std::fs::File;
 
-    let tag = region.load_chunk(0, 0).unwrap();
-    // tag.pretty_print(0, None);
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+        let utilization = region.sector_utilization().unwrap();
+        assert!(utilization > 0.0 && utilization <= 1.0);
 
-    let level = tag.key("Level").unwrap();
-    let last_update = level.key("LastUpdate").as_i64().unwrap();
-    let z_pos = level.key("zPos").as_i32().unwrap();
-    assert_eq!(last_update, 137577);
-    assert_eq!(z_pos, 0);
-    level.pretty_print(0, None);
+        // claim the one sector chunk needs three
+        let mut data = build_region(&[(0, 0, 2, b"tiny")]);
+        data[3] = 3;
+        data.resize(data.len() + 2 * 4096, 0);
+        let mut region = RegionFile::new(Cursor::new(data)).unwrap();
+        assert!((region.sector_utilization().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_external_chunks() {
+        let (data, _) = level_dat();
+        // an external chunk has just its compression type in the region
+        let region = build_region(&[(0, 0, 1, &data), (4, 9, 0x82, &[]), (5, 9, 2, &data)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        assert_eq!(region.external_chunks().unwrap(), vec![(4, 9)]);
+    }
+
+    #[test]
+    fn test_new_checked() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        assert!(RegionFile::new_checked(f).is_ok());
+
+        let (mut data, _) = level_dat();
+        assert_eq!(
+            RegionFile::new_checked(Cursor::new(&data)).err(),
+            Some(nbt_error::Error::NotARegionFile)
+        );
+
+        // big enough to have a header, but the "offsets" are gzip data
+        data.resize(3 * 4096, 0);
+        assert!(RegionFile::new(Cursor::new(&data)).is_ok());
+        assert_eq!(
+            RegionFile::new_checked(Cursor::new(&data)).err(),
+            Some(nbt_error::Error::NotARegionFile)
+        );
+    }
+
+    #[test]
+    fn test_short_timestamp_table() {
+        let data = std::fs::read("tests/data/r.0.0.mca").unwrap();
+        let full = RegionFile::from_slice(&data).unwrap();
+        // the offset table, and the first 25 timestamps
+        let short = RegionFile::from_slice(&data[..4096 + 100]).unwrap();
+
+        assert_eq!(short.offsets, full.offsets);
+        assert_eq!(short.chunk_size, full.chunk_size);
+        assert_eq!(short.timestamps[..25], full.timestamps[..25]);
+        assert!(short.timestamps[25..].iter().all(|&ts| ts == 0));
+        assert_eq!(short.get_chunk_timestamp(0, 0), Some(1383443712));
+
+        // a truncated offset table is still an error
+        assert!(RegionFile::from_slice(&data[..4000]).is_err());
+    }
+
+    #[test]
+    fn test_open_rw() {
+        use std::fs::{self, File};
+
+        let path = std::env::temp_dir().join(format!("nbtrs-open-rw-{}.mca", std::process::id()));
+        fs::copy("tests/data/r.0.0.mca", &path).unwrap();
+
+        {
+            let mut region = RegionFile::open_rw(&path).unwrap();
+            let mut chunk = region.load_chunk(0, 0).unwrap();
+            chunk
+                .set_path("Level/Edited", nbt::Tag::TagByte(1))
+                .unwrap();
+            region.write_chunk(0, 0, &chunk).unwrap();
+            region.remove_chunk(1, 12).unwrap();
+            // a brand new chunk
+            region.write_chunk(13, 23, &chunk).unwrap();
+        }
+
+        let mut region = RegionFile::new_checked(File::open(&path).unwrap()).unwrap();
+        let chunk = region.load_chunk(0, 0).unwrap();
+        assert_eq!(chunk.key("Level").key("Edited").as_i8(), Some(1));
+        assert_eq!(chunk.key("Level").key("xPos").as_i32(), Some(0));
+        assert!(region.get_chunk_timestamp(0, 0).unwrap() > 1383443712);
+        assert!(!region.chunk_exists(1, 12));
+        assert!(region.chunk_exists(13, 23));
+        assert_eq!(region.present_chunks().len(), 381);
+        region.verify().unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let data = std::fs::read("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::from_slice(&data).unwrap();
+
+        let chunk = region.load_chunk(0, 0).unwrap();
+        assert_eq!(chunk.key("Level").key("xPos").as_i32(), Some(0));
+    }
+
+    #[test]
+    fn test_dump_chunk_sectors() {
+        use std::fs::File;
+
+        let f = File::open("tests/data/r.0.0.mca").unwrap();
+        let mut region = RegionFile::new(f).unwrap();
+
+        let dump = region.dump_chunk_sectors(0, 0).unwrap().unwrap();
+        assert!(!dump.is_empty());
+        assert_eq!(dump.len() % 4096, 0);
+
+        // the dump starts with the chunk's framing
+        let len = u32::from_be_bytes([dump[0], dump[1], dump[2], dump[3]]) as usize;
+        assert!(len + 4 <= dump.len());
+        assert_eq!(dump[4], 2);
+
+        let empty = build_region(&[]);
+        let mut empty = RegionFile::new(Cursor::new(empty)).unwrap();
+        assert_eq!(empty.dump_chunk_sectors(0, 0).unwrap(), None);
+
+        // an unpadded last sector is returned as far as it goes
+        let mut bytes = build_region(&[(2, 3, 3, &[10, 0, 0, 0])]);
+        bytes.truncate(8192 + 9);
+        let mut region = RegionFile::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            region.dump_chunk_sectors(2, 3).unwrap().unwrap(),
+            [0, 0, 0, 5, 3, 10, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_chunk_longer_than_sectors() {
+        let mut data = build_region(&[(0, 0, 3, &[10, 0, 0, 0])]);
+        // claim the single sector chunk is 4 GiB long
+        data[8192..8196].copy_from_slice(&[0xff; 4]);
+        let mut region = RegionFile::new(Cursor::new(data)).unwrap();
+
+        match region.load_chunk(0, 0) {
+            Err(nbt_error::Error::ChunkLoad { source, .. }) => {
+                assert_eq!(source.io_kind(), Some(io::ErrorKind::InvalidData))
+            }
+            other => panic!("expected a ChunkLoad error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_chunk_load_error() {
+        let region = build_region(&[(5, 7, 2, b"not zlib data")]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        match region.load_chunk(5, 7) {
+            Err(nbt_error::Error::ChunkLoad { x, z, source }) => {
+                assert_eq!((x, z), (5, 7));
+                assert!(matches!(
+                    *source,
+                    nbt_error::Error::Decompression {
+                        compression_type: 2,
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("expected a ChunkLoad error"),
+        }
+    }
+
+    #[test]
+    fn test_decompression_error() {
+        // a valid zlib header, followed by a body that isn't a deflate stream
+        let region = build_region(&[(0, 0, 2, &[0x78, 0x9c, 0xff, 0xff, 0xff, 0xff])]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        let err = match region.load_chunk(0, 0) {
+            Err(nbt_error::Error::ChunkLoad { source, .. }) => *source,
+            _ => panic!("expected a ChunkLoad error"),
+        };
+        assert!(matches!(
+            err,
+            nbt_error::Error::Decompression {
+                compression_type: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_load_chunk_resilient() {
+        // level.dat is gzipped, but the chunk header claims zlib
+        let (data, expected) = level_dat();
+        let region = build_region(&[(3, 4, 2, &data)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        assert!(region.load_chunk(3, 4).is_err());
+        assert_eq!(region.load_chunk_resilient(3, 4).unwrap(), expected);
+
+        // zlib data, under a gzip or unknown compression byte
+        let mut e = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        expected.write(&mut e, "").unwrap();
+        let zlib = e.finish().unwrap();
+        let region = build_region(&[(0, 0, 1, &zlib), (1, 0, 7, &zlib)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        assert_eq!(region.load_chunk_resilient(0, 0).unwrap(), expected);
+        assert_eq!(region.load_chunk_resilient(1, 0).unwrap(), expected);
+
+        // uncompressed data that doesn't parse isn't retried, even though it's really zlib
+        let region = build_region(&[(0, 0, 3, &zlib)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+        assert!(region.load_chunk_resilient(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_mcregion_gzip_chunk() {
+        let (data, expected) = level_dat();
+        let region = build_region(&[(0, 0, 1, &data)]);
+        let mut region = RegionFile::new(Cursor::new(region)).unwrap();
+
+        assert_eq!(region.load_chunk(0, 0).unwrap(), expected);
+    }
 }